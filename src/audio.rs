@@ -0,0 +1,103 @@
+use iced::widget::{pick_list, Column, Row, Text, TextInput};
+use iced::Element;
+
+use crate::video::{AudioStream, AudioTrack, TrackMode};
+use crate::Message;
+
+/// The user's choices for one of the input's audio tracks.
+#[derive(Clone, Debug)]
+pub struct TrackRow {
+    label: String,
+    mode: TrackMode,
+    /// In dB, as typed.
+    gain: String,
+    codec: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum AudioMessage {
+    Mode(usize, TrackMode),
+    Gain(usize, String),
+}
+
+/// One row per probed track, all kept as they are.
+pub fn rows(streams: &[AudioStream]) -> Vec<TrackRow> {
+    streams
+        .iter()
+        .enumerate()
+        .map(|(index, stream)| {
+            let mut label = format!("Track {}", index + 1);
+            if let Some(title) = &stream.title {
+                label.push_str(&format!(": {title}"));
+            }
+            let mut details = vec![stream.codec.clone(), stream.layout.clone()];
+            if let Some(language) = &stream.language {
+                details.insert(0, language.clone());
+            }
+            label.push_str(&format!(" ({})", details.join(", ")));
+            TrackRow {
+                label,
+                mode: TrackMode::Keep,
+                gain: String::from("0"),
+                codec: stream.codec.clone(),
+            }
+        })
+        .collect()
+}
+
+pub fn update(rows: &mut [TrackRow], message: AudioMessage) {
+    match message {
+        AudioMessage::Mode(index, mode) => rows[index].mode = mode,
+        AudioMessage::Gain(index, gain) => rows[index].gain = gain,
+    }
+}
+
+/// Parses the rows for `TrimOptions`. With no rows, ffmpeg picks the audio track as before.
+pub fn tracks(rows: &[TrackRow]) -> Result<Vec<AudioTrack>, String> {
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let gain = match row.gain.trim() {
+                "" => 0.0,
+                gain => gain
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|gain| gain.is_finite())
+                    .ok_or_else(|| {
+                        format!("Gain for track {} must be a number of dB", index + 1)
+                    })?,
+            };
+            Ok(AudioTrack {
+                mode: row.mode,
+                gain,
+                codec: row.codec.clone(),
+            })
+        })
+        .collect()
+}
+
+pub fn view(rows: &[TrackRow]) -> Element<'_, Message> {
+    let rows = rows.iter().enumerate().map(|(index, row)| {
+        Row::new()
+            .spacing(5)
+            .align_y(iced::alignment::Vertical::Center)
+            .push(
+                Text::new(row.label.as_str())
+                    .width(iced::Length::FillPortion(3))
+                    .align_x(iced::alignment::Horizontal::Left),
+            )
+            .push(
+                pick_list(TrackMode::ALL, Some(row.mode), move |mode| {
+                    Message::Audio(AudioMessage::Mode(index, mode))
+                })
+                .width(iced::Length::FillPortion(1)),
+            )
+            .push(
+                TextInput::new("Gain (dB)", &row.gain)
+                    .width(iced::Length::FillPortion(1))
+                    .on_input(move |gain| Message::Audio(AudioMessage::Gain(index, gain))),
+            )
+            .into()
+    });
+    Column::with_children(rows).spacing(5).into()
+}
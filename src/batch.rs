@@ -0,0 +1,284 @@
+use std::path::{Path, PathBuf};
+
+use iced::widget::{pick_list, Button, Checkbox, Column, Row, Text, TextInput};
+use iced::Element;
+
+use crate::filename::sanitize;
+use crate::img;
+use crate::template::SetInfo;
+use crate::video::parse_timestamp;
+use crate::Message;
+
+/// One set to cut out of the input video.
+#[derive(Clone, Debug, Default)]
+pub struct Segment {
+    pub start: String,
+    pub end: String,
+    /// Output filename, without the `.mp4`.
+    pub name: String,
+    /// Template for this set's thumbnail, filename, bumpers and metadata.
+    pub template: String,
+    /// The set's details, copied from the form when it was added and editable afterwards.
+    pub info: SetInfo,
+    /// Whether the details are open for editing below the row.
+    pub expanded: bool,
+}
+
+/// A set detail that can be edited from a batch row.
+#[derive(Debug, Clone, Copy)]
+pub enum Detail {
+    Tournament,
+    Round,
+    Date,
+    Player1,
+    Fighter1,
+    Score1,
+    Player2,
+    Fighter2,
+    Score2,
+}
+
+impl Detail {
+    fn field(self, info: &mut SetInfo) -> &mut String {
+        match self {
+            Detail::Tournament => &mut info.tournament_name,
+            Detail::Round => &mut info.round_name,
+            Detail::Date => &mut info.date,
+            Detail::Player1 => &mut info.player_1,
+            Detail::Fighter1 => &mut info.fighter_1,
+            Detail::Score1 => &mut info.score_1,
+            Detail::Player2 => &mut info.player_2,
+            Detail::Fighter2 => &mut info.fighter_2,
+            Detail::Score2 => &mut info.score_2,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum BatchMessage {
+    Start(usize, String),
+    End(usize, String),
+    Name(usize, String),
+    Template(usize, String),
+    ToggleDetails(usize),
+    Detail(usize, Detail, String),
+    TeamMode(usize, bool),
+    Remove(usize),
+}
+
+pub fn update(segments: &mut Vec<Segment>, message: BatchMessage) {
+    match message {
+        BatchMessage::Start(index, start) => segments[index].start = start,
+        BatchMessage::End(index, end) => segments[index].end = end,
+        BatchMessage::Name(index, name) => segments[index].name = name,
+        BatchMessage::Template(index, template) => segments[index].template = template,
+        BatchMessage::ToggleDetails(index) => segments[index].expanded ^= true,
+        BatchMessage::Detail(index, detail, value) => {
+            *detail.field(&mut segments[index].info) = value;
+        }
+        BatchMessage::TeamMode(index, team_mode) => segments[index].info.team_mode = team_mode,
+        BatchMessage::Remove(index) => {
+            segments.remove(index);
+        }
+    }
+}
+
+/// Suffixes `name` with " (2)", " (3)", ... if a segment other than `row` already uses it.
+pub fn unique_name(segments: &[Segment], row: Option<usize>, name: String) -> String {
+    let taken = |name: &str| {
+        segments
+            .iter()
+            .enumerate()
+            .any(|(index, segment)| Some(index) != row && segment.name == name)
+    };
+    if !taken(&name) {
+        return name;
+    }
+    (2..)
+        .map(|n| format!("{name} ({n})"))
+        .find(|candidate| !taken(candidate))
+        .expect("There is always an unused name")
+}
+
+/// Parses the segments into `(start, end, output)` ranges for `trim_segments`, refusing to
+/// overwrite a file that is already there.
+pub fn ranges(
+    segments: &[Segment],
+    output_folder: &Path,
+) -> Result<Vec<(f64, f64, PathBuf)>, String> {
+    let ranges: Vec<(f64, f64, PathBuf)> = segments
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            let row = index + 1;
+            let start = parse_timestamp(&segment.start)
+                .ok_or_else(|| format!("Invalid starting time in batch row {row}"))?;
+            let end = parse_timestamp(&segment.end)
+                .ok_or_else(|| format!("Invalid ending time in batch row {row}"))?;
+            let name = sanitize(segment.name.trim());
+            if name.is_empty() {
+                return Err(format!("Batch row {row} needs a filename"));
+            }
+            Ok((start, end, output_folder.join(format!("{name}.mp4"))))
+        })
+        .collect::<Result<_, String>>()?;
+    for (index, range) in ranges.iter().enumerate() {
+        if ranges[..index].iter().any(|other| other.2 == range.2) {
+            return Err(format!(
+                "More than one batch row writes {}",
+                range.2.display()
+            ));
+        }
+        if range.2.exists() {
+            return Err(format!("{} already exists", range.2.display()));
+        }
+    }
+    Ok(ranges)
+}
+
+/// A one-line summary of a row's details, so a wrong copy of the form is easy to spot.
+fn summary(info: &SetInfo) -> String {
+    let mut summary = format!(
+        "{} ({}) vs {} ({})",
+        info.player_1, info.fighter_1, info.player_2, info.fighter_2
+    );
+    if !info.score_1.is_empty() || !info.score_2.is_empty() {
+        summary.push_str(&format!(", {}-{}", info.score_1, info.score_2));
+    }
+    for detail in [&info.round_name, &info.tournament_name, &info.date] {
+        if !detail.is_empty() {
+            summary.push_str(", ");
+            summary.push_str(detail);
+        }
+    }
+    summary
+}
+
+/// Inputs for editing a row's details.
+fn details(index: usize, segment: &Segment) -> Element<'_, Message> {
+    let detail = move |detail: Detail| {
+        move |value| Message::Batch(BatchMessage::Detail(index, detail, value))
+    };
+    let input = |placeholder, value, field| {
+        TextInput::new(placeholder, value)
+            .width(iced::Length::FillPortion(2))
+            .on_input(detail(field))
+    };
+    let score = |value, field| {
+        TextInput::new("Score", value)
+            .width(iced::Length::FillPortion(1))
+            .on_input(detail(field))
+    };
+    let fighters = img::characters(&segment.template);
+    let info = &segment.info;
+    let row = || {
+        Row::new()
+            .spacing(5)
+            .align_y(iced::alignment::Vertical::Center)
+    };
+    Column::new()
+        .spacing(5)
+        .push(
+            row()
+                .push(input(
+                    "Tournament Name",
+                    &info.tournament_name,
+                    Detail::Tournament,
+                ))
+                .push(input("Round Name", &info.round_name, Detail::Round))
+                .push(input("Date", &info.date, Detail::Date))
+                .push(
+                    Checkbox::new("Team Mode", info.team_mode)
+                        .on_toggle(move |team_mode| {
+                            Message::Batch(BatchMessage::TeamMode(index, team_mode))
+                        })
+                        .width(iced::Length::FillPortion(1)),
+                ),
+        )
+        .push(
+            row()
+                .push(input("Player 1", &info.player_1, Detail::Player1))
+                .push(score(&info.score_1, Detail::Score1))
+                .push(
+                    pick_list(
+                        fighters.clone(),
+                        Some(info.fighter_1.clone()),
+                        detail(Detail::Fighter1),
+                    )
+                    .width(iced::Length::FillPortion(1)),
+                )
+                .push(input("Player 2", &info.player_2, Detail::Player2))
+                .push(score(&info.score_2, Detail::Score2))
+                .push(
+                    pick_list(
+                        fighters,
+                        Some(info.fighter_2.clone()),
+                        detail(Detail::Fighter2),
+                    )
+                    .width(iced::Length::FillPortion(1)),
+                ),
+        )
+        .into()
+}
+
+pub fn view(segments: &[Segment]) -> Element<'_, Message> {
+    let rows = segments.iter().enumerate().map(|(index, segment)| {
+        let batch = move |message: fn(usize, String) -> BatchMessage| {
+            move |value| Message::Batch(message(index, value))
+        };
+        let row = Row::new()
+            .spacing(5)
+            .align_y(iced::alignment::Vertical::Center)
+            .padding(iced::Padding::new(10.0).top(0.0))
+            .push(
+                TextInput::new("Start", &segment.start)
+                    .width(iced::Length::FillPortion(1))
+                    .on_input(batch(BatchMessage::Start)),
+            )
+            .push(
+                TextInput::new("End", &segment.end)
+                    .width(iced::Length::FillPortion(1))
+                    .on_input(batch(BatchMessage::End)),
+            )
+            .push(
+                TextInput::new("Filename", &segment.name)
+                    .width(iced::Length::FillPortion(3))
+                    .on_input(batch(BatchMessage::Name)),
+            )
+            .push(
+                pick_list(
+                    img::template_names(),
+                    Some(segment.template.clone()),
+                    batch(BatchMessage::Template),
+                )
+                .width(iced::Length::FillPortion(2)),
+            )
+            .push(
+                Button::new(Text::new(if segment.expanded { "Hide" } else { "Details" }))
+                    .width(100.0)
+                    .on_press(Message::Batch(BatchMessage::ToggleDetails(index))),
+            )
+            .push(
+                Button::new(Text::new("Remove"))
+                    .width(100.0)
+                    .on_press(Message::Batch(BatchMessage::Remove(index))),
+            );
+        let below = if segment.expanded {
+            details(index, segment)
+        } else {
+            Text::new(summary(&segment.info))
+                .size(14)
+                .width(iced::Length::Fill)
+                .into()
+        };
+        Column::new()
+            .push(row)
+            .push(
+                Row::new()
+                    .padding(iced::Padding::new(10.0).top(0.0).left(40.0))
+                    .push(below),
+            )
+            .into()
+    });
+    Column::with_children(rows).into()
+}
@@ -0,0 +1,196 @@
+use image::imageops::overlay;
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+
+/// How a layer's colors are combined with the layers below it.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+    SoftLight,
+}
+
+impl BlendMode {
+    /// Blends a single channel, with `backdrop` and `source` from 0.0 to 1.0.
+    fn blend(&self, backdrop: f32, source: f32) -> f32 {
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => backdrop * source,
+            BlendMode::Screen => screen(backdrop, source),
+            BlendMode::Overlay => {
+                if backdrop <= 0.5 {
+                    source * 2.0 * backdrop
+                } else {
+                    screen(source, 2.0 * backdrop - 1.0)
+                }
+            }
+            BlendMode::Add => (backdrop + source).min(1.0),
+            BlendMode::SoftLight => {
+                if source <= 0.5 {
+                    backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+                } else {
+                    let d = if backdrop <= 0.25 {
+                        ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
+                    } else {
+                        backdrop.sqrt()
+                    };
+                    backdrop + (2.0 * source - 1.0) * (d - backdrop)
+                }
+            }
+        }
+    }
+}
+
+fn screen(backdrop: f32, source: f32) -> f32 {
+    backdrop + source - backdrop * source
+}
+
+/// Draws `top` over `bottom` with its top left corner at `x`, `y`, using `mode` and following
+/// the W3C compositing formulas. Like `overlay`, only the part of `top` inside `bottom` is drawn.
+pub fn composite(bottom: &mut RgbaImage, top: &RgbaImage, x: i64, y: i64, mode: BlendMode) {
+    if mode == BlendMode::Normal {
+        overlay(bottom, top, x, y);
+        return;
+    }
+    let columns = x.max(0)..(x + top.width() as i64).min(bottom.width() as i64);
+    let rows = y.max(0)..(y + top.height() as i64).min(bottom.height() as i64);
+    for row in rows {
+        for column in columns.clone() {
+            let source = *top.get_pixel((column - x) as u32, (row - y) as u32);
+            let backdrop = bottom.get_pixel_mut(column as u32, row as u32);
+            *backdrop = blend_pixel(*backdrop, source, mode);
+        }
+    }
+}
+
+fn blend_pixel(backdrop: Rgba<u8>, source: Rgba<u8>, mode: BlendMode) -> Rgba<u8> {
+    let alpha_b = backdrop.0[3] as f32 / 255.0;
+    let alpha_s = source.0[3] as f32 / 255.0;
+    let alpha_o = alpha_s + alpha_b * (1.0 - alpha_s);
+    if alpha_o <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let mut out = [0; 4];
+    for ((out, &cb), &cs) in out.iter_mut().zip(&backdrop.0).zip(&source.0).take(3) {
+        let cb = cb as f32 / 255.0;
+        let cs = cs as f32 / 255.0;
+        let mixed = alpha_s * (1.0 - alpha_b) * cs
+            + alpha_s * alpha_b * mode.blend(cb, cs)
+            + (1.0 - alpha_s) * alpha_b * cb;
+        *out = (mixed / alpha_o * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    out[3] = (alpha_o * 255.0).round() as u8;
+    Rgba(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKDROP: Rgba<u8> = Rgba([200, 100, 30, 255]);
+    const SOURCE: Rgba<u8> = Rgba([100, 200, 150, 255]);
+
+    fn blend(mode: BlendMode) -> [u8; 4] {
+        let mut bottom = RgbaImage::from_pixel(1, 1, BACKDROP);
+        composite(
+            &mut bottom,
+            &RgbaImage::from_pixel(1, 1, SOURCE),
+            0,
+            0,
+            mode,
+        );
+        bottom.get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn normal_replaces_opaque_pixels() {
+        assert_eq!(blend(BlendMode::Normal), SOURCE.0);
+    }
+
+    #[test]
+    fn multiply() {
+        assert_eq!(blend(BlendMode::Multiply), [78, 78, 18, 255]);
+    }
+
+    #[test]
+    fn screen() {
+        assert_eq!(blend(BlendMode::Screen), [222, 222, 162, 255]);
+    }
+
+    #[test]
+    fn overlay() {
+        assert_eq!(blend(BlendMode::Overlay), [188, 157, 35, 255]);
+    }
+
+    #[test]
+    fn add_clamps() {
+        assert_eq!(blend(BlendMode::Add), [255, 255, 180, 255]);
+    }
+
+    #[test]
+    fn soft_light() {
+        assert_eq!(blend(BlendMode::SoftLight), [191, 134, 40, 255]);
+    }
+
+    #[test]
+    fn transparent_source_keeps_backdrop() {
+        let mut bottom = RgbaImage::from_pixel(1, 1, BACKDROP);
+        let top = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 0]));
+        composite(&mut bottom, &top, 0, 0, BlendMode::Multiply);
+        assert_eq!(bottom.get_pixel(0, 0).0, BACKDROP.0);
+    }
+
+    #[test]
+    fn half_transparent_source_mixes_with_backdrop() {
+        let mut bottom = RgbaImage::from_pixel(1, 1, BACKDROP);
+        let top = RgbaImage::from_pixel(1, 1, Rgba([100, 200, 150, 128]));
+        composite(&mut bottom, &top, 0, 0, BlendMode::Multiply);
+        assert_eq!(bottom.get_pixel(0, 0).0, [139, 89, 24, 255]);
+    }
+
+    #[test]
+    fn transparent_backdrop_shows_source_unblended() {
+        let mut bottom = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 0]));
+        composite(
+            &mut bottom,
+            &RgbaImage::from_pixel(1, 1, SOURCE),
+            0,
+            0,
+            BlendMode::Screen,
+        );
+        assert_eq!(bottom.get_pixel(0, 0).0, SOURCE.0);
+    }
+
+    #[test]
+    fn smaller_layer_is_drawn_at_its_offset() {
+        let mut bottom = RgbaImage::from_pixel(3, 3, BACKDROP);
+        let top = RgbaImage::from_pixel(1, 2, SOURCE);
+        composite(&mut bottom, &top, 1, 1, BlendMode::Multiply);
+        for (x, y, pixel) in bottom.enumerate_pixels() {
+            let expected = if x == 1 && y >= 1 {
+                [78, 78, 18, 255]
+            } else {
+                BACKDROP.0
+            };
+            assert_eq!(pixel.0, expected, "pixel at {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn larger_layer_is_clipped_to_the_canvas() {
+        let mut bottom = RgbaImage::from_pixel(2, 2, BACKDROP);
+        let mut top = RgbaImage::from_pixel(4, 3, SOURCE);
+        // Only this pixel lands on the canvas's top right corner.
+        top.put_pixel(3, 1, Rgba([0, 0, 0, 0]));
+        composite(&mut bottom, &top, -2, -1, BlendMode::Multiply);
+        assert_eq!(bottom.get_pixel(0, 0).0, [78, 78, 18, 255]);
+        assert_eq!(bottom.get_pixel(1, 0).0, BACKDROP.0);
+        assert_eq!(bottom.get_pixel(0, 1).0, [78, 78, 18, 255]);
+        assert_eq!(bottom.get_pixel(1, 1).0, [78, 78, 18, 255]);
+    }
+}
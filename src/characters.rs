@@ -0,0 +1,80 @@
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::img::parse_color;
+
+/// Optional file in `char_img_path` describing the character images, keyed by filename.
+pub const METADATA_FILE: &str = "characters.json";
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "bmp"];
+
+/// Pixels more transparent than this are ignored when finding the dominant color.
+const MIN_ALPHA: u8 = 128;
+
+/// How much a grey, black or white pixel counts compared to a fully saturated one, so
+/// that outlines and highlights only win when the art has no real color.
+const GREY_WEIGHT: f32 = 0.05;
+
+/// Extra information about a character image.
+#[derive(Deserialize, Clone, Default)]
+pub struct CharacterMetadata {
+    /// `#RRGGBB` color used for `{fighter1.accent}` / `{fighter2.accent}`.
+    #[serde(default)]
+    pub accent: Option<String>,
+}
+
+/// Reads `characters.json` from `dir`, or nothing if the file doesn't exist.
+pub fn load_metadata(dir: &Path) -> Result<HashMap<String, CharacterMetadata>, String> {
+    let path = dir.join(METADATA_FILE);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let data =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let metadata: HashMap<String, CharacterMetadata> = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+    metadata
+        .iter()
+        .filter_map(|(name, character)| Some((name, character.accent.as_deref()?)))
+        .try_for_each(|(name, accent)| {
+            parse_color(accent)
+                .map(|_| ())
+                .map_err(|e| format!("Error in {} for \"{name}\": {e}", path.display()))
+        })?;
+    Ok(metadata)
+}
+
+/// Whether `filename` looks like a character image rather than metadata or other files.
+pub fn is_image(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// The most prominent color of the visible part of `img`, favoring saturated colors
+/// over outlines and highlights. `None` if the image is fully transparent.
+pub fn dominant_color(img: &RgbaImage) -> Option<Rgba<u8>> {
+    // Group similar colors by their top 4 bits per channel.
+    let mut buckets: HashMap<[u8; 3], (f32, [u64; 3], u64)> = HashMap::new();
+    for pixel in img.pixels().filter(|pixel| pixel.0[3] >= MIN_ALPHA) {
+        let [r, g, b, _] = pixel.0;
+        let chroma = (r.max(g).max(b) - r.min(g).min(b)) as f32 / 255.0;
+        let (weight, sum, count) = buckets.entry([r >> 4, g >> 4, b >> 4]).or_default();
+        *weight += GREY_WEIGHT + chroma;
+        sum[0] += r as u64;
+        sum[1] += g as u64;
+        sum[2] += b as u64;
+        *count += 1;
+    }
+    let (_, sum, count) = buckets.into_values().max_by(|a, b| a.0.total_cmp(&b.0))?;
+    Some(Rgba([
+        (sum[0] / count) as u8,
+        (sum[1] / count) as u8,
+        (sum[2] / count) as u8,
+        255,
+    ]))
+}
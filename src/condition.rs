@@ -0,0 +1,351 @@
+use crate::template::SetInfo;
+
+/// Field names accepted in addition to the template tokens.
+const ALIASES: [(&str, &str); 2] = [("round_name", "round"), ("tournament_name", "tournament")];
+
+#[derive(Debug, PartialEq)]
+enum Word {
+    Ident(String),
+    Str(String),
+    Eq,
+    NotEq,
+    Open,
+    Close,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    TeamMode,
+    Present(String),
+    Contains(String, String),
+    Equals(String, String),
+}
+
+/// Checks that a `when` expression is well formed and only uses known fields.
+pub fn validate(expr: &str) -> Result<(), String> {
+    parse(expr).map(|_| ())
+}
+
+/// Evaluates a `when` expression such as `round contains "Grand" and not team mode`.
+pub fn evaluate(expr: &str, info: &SetInfo) -> Result<bool, String> {
+    Ok(parse(expr)?.evaluate(info))
+}
+
+fn parse(expr: &str) -> Result<Expr, String> {
+    let words = tokenize(expr).map_err(|e| format!("{e} in condition \"{expr}\""))?;
+    let mut parser = Parser { words, pos: 0 };
+    let parsed = parser
+        .or()
+        .and_then(|parsed| match parser.words.get(parser.pos) {
+            None => Ok(parsed),
+            Some(word) => Err(format!("Unexpected {word:?}")),
+        })
+        .map_err(|e| format!("{e} in condition \"{expr}\""))?;
+    Ok(parsed)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Word>, String> {
+    let mut words = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                words.push(Word::Open);
+            }
+            ')' => {
+                chars.next();
+                words.push(Word::Close);
+            }
+            '=' | '!' => {
+                chars.next();
+                if chars.next() != Some('=') {
+                    return Err(format!("Expected '=' after '{c}'"));
+                }
+                words.push(if c == '=' { Word::Eq } else { Word::NotEq });
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(String::from("Unclosed quote")),
+                    }
+                }
+                words.push(Word::Str(text));
+            }
+            _ => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '.' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if ident.is_empty() {
+                    return Err(format!("Unexpected '{c}'"));
+                }
+                words.push(Word::Ident(ident));
+            }
+        }
+    }
+    Ok(words)
+}
+
+struct Parser {
+    words: Vec<Word>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<&Word> {
+        let word = self.words.get(self.pos);
+        self.pos += 1;
+        word
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.words.get(self.pos), Some(Word::Ident(ident)) if ident == keyword)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.words.get(self.pos) == Some(&Word::Open) {
+            self.pos += 1;
+            let expr = self.or()?;
+            return match self.next() {
+                Some(Word::Close) => Ok(expr),
+                _ => Err(String::from("Expected ')'")),
+            };
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let field = match self.next() {
+            Some(Word::Ident(ident)) => ident.clone(),
+            Some(word) => return Err(format!("Unexpected {word:?}")),
+            None => return Err(String::from("Unexpected end")),
+        };
+        if field == "team" {
+            return match self.next() {
+                Some(Word::Ident(ident)) if ident == "mode" => Ok(Expr::TeamMode),
+                _ => Err(String::from("Expected \"team mode\"")),
+            };
+        }
+        let field = resolve_field(&field)?;
+        match self.next() {
+            Some(Word::Ident(ident)) if ident == "present" => Ok(Expr::Present(field)),
+            _ if field == "score" => Err(String::from("Expected \"score present\"")),
+            Some(Word::Ident(ident)) if ident == "contains" => {
+                Ok(Expr::Contains(field, self.string()?))
+            }
+            Some(Word::Eq) => Ok(Expr::Equals(field, self.string()?)),
+            Some(Word::NotEq) => Ok(Expr::Not(Box::new(Expr::Equals(field, self.string()?)))),
+            _ => Err(format!(
+                "Expected \"present\", \"contains\", \"==\" or \"!=\" after {field}"
+            )),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Word::Str(text)) => Ok(text.clone()),
+            _ => Err(String::from("Expected a quoted string")),
+        }
+    }
+}
+
+/// `score` stands for both scores; everything else must be a template token.
+fn resolve_field(field: &str) -> Result<String, String> {
+    let field = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == field)
+        .map_or(field, |(_, token)| token);
+    if field == "score" || SetInfo::is_token(field) {
+        Ok(field.to_string())
+    } else {
+        Err(format!("Unknown field \"{field}\""))
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, info: &SetInfo) -> bool {
+        match self {
+            Expr::Or(a, b) => a.evaluate(info) || b.evaluate(info),
+            Expr::And(a, b) => a.evaluate(info) && b.evaluate(info),
+            Expr::Not(a) => !a.evaluate(info),
+            Expr::TeamMode => info.team_mode,
+            Expr::Present(field) if field == "score" => {
+                !info.score_1.trim().is_empty() && !info.score_2.trim().is_empty()
+            }
+            Expr::Present(field) => !value(info, field).trim().is_empty(),
+            Expr::Contains(field, text) => fold(&value(info, field)).contains(&fold(text)),
+            Expr::Equals(field, text) => fold(&value(info, field)) == fold(text),
+        }
+    }
+}
+
+/// Comparisons ignore case, including outside ASCII.
+fn fold(text: &str) -> String {
+    text.to_lowercase()
+}
+
+fn value(info: &SetInfo, field: &str) -> String {
+    info.lookup(field).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> SetInfo {
+        SetInfo {
+            tournament_name: String::from("Big House"),
+            round_name: String::from("Grand Finals"),
+            player_1: String::from("Mickey"),
+            player_2: String::from("Popeye"),
+            score_1: String::from("3"),
+            score_2: String::new(),
+            ..SetInfo::default()
+        }
+    }
+
+    fn check(expr: &str) -> bool {
+        evaluate(expr, &info()).unwrap()
+    }
+
+    #[test]
+    fn tokenizes_every_kind_of_word() {
+        assert_eq!(
+            tokenize(r#"(fighter1.accent == "a b")!=not"#).unwrap(),
+            [
+                Word::Open,
+                Word::Ident(String::from("fighter1.accent")),
+                Word::Eq,
+                Word::Str(String::from("a b")),
+                Word::Close,
+                Word::NotEq,
+                Word::Ident(String::from("not")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lone_equals_sign_is_an_error() {
+        assert!(validate(r#"round = "Pools""#)
+            .unwrap_err()
+            .contains("Expected '='"));
+    }
+
+    #[test]
+    fn unterminated_string_is_an_error() {
+        assert!(validate(r#"round == "Pools"#)
+            .unwrap_err()
+            .contains("Unclosed quote"));
+    }
+
+    #[test]
+    fn unknown_field_is_an_error() {
+        assert!(validate("player3 present")
+            .unwrap_err()
+            .contains("Unknown field"));
+    }
+
+    #[test]
+    fn trailing_words_are_an_error() {
+        assert!(validate("round present player1").is_err());
+        assert!(validate("(round present")
+            .unwrap_err()
+            .contains("Expected ')'"));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // Read as `round present or (date present and team mode)`.
+        assert!(check("round present or date present and team mode"));
+        assert!(!check("(round present or date present) and team mode"));
+    }
+
+    #[test]
+    fn not_binds_tightest() {
+        assert!(!check("not round present and player1 present"));
+        assert!(check("not (round present and date present)"));
+        assert!(check("not not round present"));
+    }
+
+    #[test]
+    fn team_mode() {
+        assert!(!check("team mode"));
+        let info = SetInfo {
+            team_mode: true,
+            ..info()
+        };
+        assert!(evaluate("team mode", &info).unwrap());
+        assert!(validate("team").unwrap_err().contains("team mode"));
+    }
+
+    #[test]
+    fn score_present_needs_both_scores() {
+        assert!(!check("score present"));
+        assert!(check("score1 present"));
+        let info = SetInfo {
+            score_2: String::from("0"),
+            ..info()
+        };
+        assert!(evaluate("score present", &info).unwrap());
+        assert!(validate(r#"score == "3""#)
+            .unwrap_err()
+            .contains("score present"));
+    }
+
+    #[test]
+    fn aliases_name_the_same_fields() {
+        assert!(check(r#"round_name contains "grand""#));
+        assert!(check(r#"tournament_name == "big house""#));
+    }
+
+    #[test]
+    fn comparisons_ignore_case() {
+        assert!(check(r#"round contains "FINALS""#));
+        assert!(check(r#"round == "grand finals""#));
+        assert!(check(r#"round != "Pools""#));
+        let info = SetInfo {
+            round_name: String::from("élite Top 8"),
+            ..info()
+        };
+        assert!(evaluate(r#"round contains "ÉLITE""#, &info).unwrap());
+        assert!(evaluate(r#"round == "ÉLITE TOP 8""#, &info).unwrap());
+    }
+}
@@ -0,0 +1,281 @@
+use std::process::Command;
+
+use image::imageops::{self, FilterType};
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+
+use crate::timeline::Timeline;
+use crate::video::FFMPEG_PATH;
+
+/// Size the reference image and sampled frames are shrunk to before comparing them.
+const REFERENCE_WIDTH: u32 = 64;
+const REFERENCE_HEIGHT: u32 = 36;
+/// Frames per second compared against the reference image.
+const REFERENCE_RATE: f64 = 2.0;
+/// Frames per second checked for scene changes and black screens.
+const SCENE_RATE: f64 = 5.0;
+/// Boundaries closer together than this, in seconds, are treated as one.
+const MERGE_GAP: f64 = 5.0;
+/// How far, in seconds, a set's start or end may move to line up with a scene change.
+const SNAP_WINDOW: f64 = 5.0;
+
+/// Tuning for the analysis pass that proposes set boundaries, stored in `static/settings.json`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct DetectionSettings {
+    /// How different consecutive frames must be, from 0 to 1, to count as a scene change.
+    pub scene_threshold: f64,
+    /// Shortest black screen, in seconds, that separates sets.
+    pub black_duration: f64,
+    /// Audio quieter than this, in dB, counts as silence.
+    pub silence_level: f64,
+    /// Shortest silence, in seconds, that separates sets.
+    pub silence_duration: f64,
+    /// A screen shown between sets, such as "GAME!" or character select.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_image: Option<String>,
+    /// How close a frame must be to the reference image, from 0 (identical) to 1, to match.
+    pub reference_threshold: f64,
+    /// Shorter stretches between boundaries, in seconds, aren't proposed as sets.
+    pub min_set_length: f64,
+}
+
+impl Default for DetectionSettings {
+    fn default() -> Self {
+        DetectionSettings {
+            scene_threshold: 0.4,
+            black_duration: 0.5,
+            silence_level: -50.0,
+            silence_duration: 3.0,
+            reference_image: None,
+            reference_threshold: 0.1,
+            min_set_length: 120.0,
+        }
+    }
+}
+
+/// Scans the timeline for likely gaps between sets and returns the `(start, end)` of each
+/// stretch between them, in seconds.
+pub fn detect_sets(
+    timeline: &Timeline,
+    settings: &DetectionSettings,
+) -> Result<Vec<(f64, f64)>, String> {
+    let reference = settings
+        .reference_image
+        .as_deref()
+        .map(load_reference)
+        .transpose()?;
+    let mut gaps = Vec::new();
+    let mut scenes = Vec::new();
+    for (input, info, offset) in timeline.file_details() {
+        let analysis = analyze(input, !info.audio.is_empty(), settings, reference.as_ref())?;
+        gaps.extend(
+            analysis
+                .gaps
+                .into_iter()
+                .map(|(start, end)| (start + offset, end + offset)),
+        );
+        scenes.extend(analysis.scenes.into_iter().map(|time| time + offset));
+    }
+    Ok(
+        sets_between(gaps, timeline.duration(), settings.min_set_length)
+            .into_iter()
+            .map(|(start, end)| snap(start, end, &scenes))
+            .collect(),
+    )
+}
+
+/// What was found in one file, in that file's own times.
+struct Analysis {
+    /// Spans of black, silence or reference matches.
+    gaps: Vec<(f64, f64)>,
+    scenes: Vec<f64>,
+}
+
+fn analyze(
+    input: &str,
+    has_audio: bool,
+    settings: &DetectionSettings,
+    reference: Option<&GrayImage>,
+) -> Result<Analysis, String> {
+    let output = Command::new(FFMPEG_PATH)
+        .args(analysis_args(
+            input,
+            has_audio,
+            settings,
+            reference.is_some(),
+        ))
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("Could not analyze {input}"));
+    }
+    let details = String::from_utf8_lossy(&output.stderr);
+
+    let mut gaps: Vec<(f64, f64)> = values(&details, "black_start:")
+        .zip(values(&details, "black_end:"))
+        .chain(values(&details, "silence_start:").zip(values(&details, "silence_end:")))
+        .collect();
+    if let Some(reference) = reference {
+        gaps.extend(reference_matches(
+            &output.stdout,
+            reference,
+            settings.reference_threshold,
+        ));
+    }
+    let scenes: Vec<f64> = details
+        .lines()
+        .filter(|line| line.contains("Parsed_metadata"))
+        .flat_map(|line| values(line, "pts_time:"))
+        .collect();
+    Ok(Analysis { gaps, scenes })
+}
+
+/// The ffmpeg arguments for one analysis pass. ffmpeg refuses an audio filter when there's no
+/// audio stream to apply it to, so silence is only listened for in files that have one.
+fn analysis_args(
+    input: &str,
+    has_audio: bool,
+    settings: &DetectionSettings,
+    reference: bool,
+) -> Vec<String> {
+    let mut args: Vec<String> = ["-hide_banner", "-nostats", "-i", input, "-map", "0:v:0"]
+        .map(String::from)
+        .into();
+    args.push(String::from("-vf"));
+    args.push(format!(
+        "fps={SCENE_RATE},scale=160:-2,blackdetect=d={}:pix_th=0.10,\
+         select='gt(scene,{})',metadata=print",
+        settings.black_duration, settings.scene_threshold
+    ));
+    if has_audio {
+        args.extend(["-map", "0:a:0", "-af"].map(String::from));
+        args.push(format!(
+            "silencedetect=n={}dB:d={}",
+            settings.silence_level, settings.silence_duration
+        ));
+    }
+    args.extend(["-f", "null", "-"].map(String::from));
+    if reference {
+        // Tiny greyscale frames on stdout, from the same decode.
+        args.extend(["-map", "0:v:0", "-vf"].map(String::from));
+        args.push(format!(
+            "fps={REFERENCE_RATE},scale={REFERENCE_WIDTH}:{REFERENCE_HEIGHT},format=gray"
+        ));
+        args.extend(["-f", "rawvideo", "pipe:1"].map(String::from));
+    }
+    args
+}
+
+/// Loads the reference image at the size frames are compared at.
+fn load_reference(path: &str) -> Result<GrayImage, String> {
+    let img =
+        image::open(path).map_err(|e| format!("Failed to open reference image {path}: {e}"))?;
+    Ok(imageops::resize(
+        &img.to_luma8(),
+        REFERENCE_WIDTH,
+        REFERENCE_HEIGHT,
+        FilterType::Triangle,
+    ))
+}
+
+/// The time spans where sampled frames look like `reference`.
+fn reference_matches(frames: &[u8], reference: &GrayImage, threshold: f64) -> Vec<(f64, f64)> {
+    let frame_length = 1.0 / REFERENCE_RATE;
+    frames
+        .chunks_exact(reference.as_raw().len())
+        .enumerate()
+        .filter(|(_, frame)| {
+            let difference: u64 = frame
+                .iter()
+                .zip(reference.as_raw())
+                .map(|(a, b)| a.abs_diff(*b) as u64)
+                .sum();
+            difference as f64 / (frame.len() as f64 * 255.0) < threshold
+        })
+        .map(|(index, _)| {
+            let time = index as f64 * frame_length;
+            (time, time + frame_length)
+        })
+        .collect()
+}
+
+/// Numbers following each occurrence of `key` in ffmpeg's log, in order.
+fn values<'a>(details: &'a str, key: &'a str) -> impl Iterator<Item = f64> + 'a {
+    details.split(key).skip(1).filter_map(|rest| {
+        rest.split_whitespace()
+            .next()
+            .and_then(|value| value.parse().ok())
+    })
+}
+
+/// Merges overlapping or nearby gaps and returns the long enough stretches between them.
+fn sets_between(mut gaps: Vec<(f64, f64)>, duration: f64, min_length: f64) -> Vec<(f64, f64)> {
+    gaps.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut merged: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in gaps {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + MERGE_GAP => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    let mut sets = Vec::new();
+    let mut set_start = 0.0;
+    for (start, end) in merged {
+        sets.push((set_start, start));
+        set_start = end;
+    }
+    sets.push((set_start, duration));
+    sets.retain(|(start, end)| end - start >= min_length);
+    sets
+}
+
+/// Moves the start to the first scene change just after it, and the end to the last one
+/// just before it, since a cut there is usually cleaner than where the gap was detected.
+fn snap(start: f64, end: f64, scenes: &[f64]) -> (f64, f64) {
+    let start = scenes
+        .iter()
+        .copied()
+        .find(|time| *time >= start && *time <= start + SNAP_WINDOW)
+        .unwrap_or(start);
+    let end = scenes
+        .iter()
+        .copied()
+        .rev()
+        .find(|time| *time <= end && *time >= end - SNAP_WINDOW)
+        .unwrap_or(end);
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has(args: &[String], arg: &str) -> bool {
+        args.iter().any(|a| a.starts_with(arg))
+    }
+
+    #[test]
+    fn video_only_files_skip_silence_detection() {
+        let args = analysis_args("in.mp4", false, &DetectionSettings::default(), false);
+        assert!(!has(&args, "-af"));
+        assert!(!has(&args, "silencedetect"));
+        assert!(!has(&args, "0:a"));
+        assert!(has(&args, "fps=5,scale=160:-2,blackdetect"));
+    }
+
+    #[test]
+    fn files_with_audio_listen_for_silence() {
+        let args = analysis_args("in.mp4", true, &DetectionSettings::default(), false);
+        let filter = args.iter().position(|a| a == "-af").unwrap();
+        assert_eq!(args[filter - 1], "0:a:0");
+        assert_eq!(args[filter + 1], "silencedetect=n=-50dB:d=3");
+    }
+
+    #[test]
+    fn reference_frames_go_to_stdout() {
+        let args = analysis_args("in.mp4", false, &DetectionSettings::default(), true);
+        assert_eq!(args.last().unwrap(), "pipe:1");
+        assert_eq!(args.iter().filter(|a| *a == "0:v:0").count(), 2);
+    }
+}
@@ -0,0 +1,523 @@
+use iced::mouse;
+use iced::widget::canvas::{event, Canvas, Event, Frame, Geometry, Path, Program, Stroke};
+use iced::widget::image::Handle;
+use iced::widget::{Button, Column, Row, Text, TextInput};
+use iced::{Color, Element, Point, Rectangle, Renderer, Size, Task, Theme, Vector};
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::task;
+
+use crate::img::{self, Assets, LayoutItem, Placements};
+use crate::template::SetInfo;
+use crate::Message;
+
+/// Shared by every editor, so a render finishing after its editor was closed and another
+/// opened is still recognized as stale.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// How a property typed into the side panel is written to the template.
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    OptionalText,
+    Integer,
+    OptionalInteger,
+    Number,
+    OptionalNumber,
+}
+
+const TEXT_FIELDS: [(&str, Kind); 12] = [
+    ("text", Kind::Text),
+    ("x", Kind::Integer),
+    ("y", Kind::Integer),
+    ("scale", Kind::Number),
+    ("theta", Kind::Number),
+    ("color", Kind::OptionalText),
+    ("letter_spacing", Kind::OptionalNumber),
+    ("skew_x", Kind::OptionalNumber),
+    ("transform", Kind::OptionalText),
+    ("max_width", Kind::OptionalInteger),
+    ("line_height", Kind::OptionalNumber),
+    ("align", Kind::OptionalText),
+];
+
+const LAYER_FIELDS: [(&str, Kind); 4] = [
+    ("path", Kind::OptionalText),
+    ("x", Kind::OptionalInteger),
+    ("y", Kind::OptionalInteger),
+    ("blend", Kind::OptionalText),
+];
+
+#[derive(Clone, Debug)]
+pub enum EditorMessage {
+    /// The mouse was pressed at this point on the thumbnail, in template pixels.
+    Press(Point),
+    DragTo(Point),
+    Release,
+    Field(&'static str, String),
+    /// A finished render of the given revision of the template.
+    Rendered(u64, Result<Preview, String>),
+}
+
+/// A rendered thumbnail, its size, where each drawn item ended up, and the assets it used.
+pub type Preview = (Handle, Size, Placements, Arc<Assets>);
+
+/// A template being edited: its JSON, a rendered preview and the current selection.
+#[derive(Clone)]
+pub struct Editor {
+    path: PathBuf,
+    /// The template as written in its file, so unknown fields and their order survive saving.
+    document: Value,
+    info: SetInfo,
+    preview: Option<Handle>,
+    canvas_size: Size,
+    items: Placements,
+    /// The font and characters of the last render, so edits don't reload them from disk.
+    assets: Option<Arc<Assets>>,
+    selected: Option<LayoutItem>,
+    fields: Vec<(&'static str, String)>,
+    drag: Option<Drag>,
+    /// Changed on every edit, so renders of older versions of the template are dropped.
+    revision: u64,
+    pub message: String,
+}
+
+#[derive(Clone, Copy)]
+struct Drag {
+    start: Point,
+    offset: Vector,
+}
+
+impl Editor {
+    /// Opens the file behind `template`, previewed with `info` plus placeholders for empty
+    /// entries. The preview is rendered by the returned task.
+    pub fn open(template: &str, info: SetInfo) -> Result<(Editor, Task<Message>), String> {
+        let path = img::template_source(template)?;
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let document = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+        let mut editor = Editor {
+            path,
+            document,
+            info: with_placeholders(info),
+            preview: None,
+            canvas_size: Size::ZERO,
+            items: Vec::new(),
+            assets: None,
+            selected: None,
+            fields: Vec::new(),
+            drag: None,
+            revision: 0,
+            message: String::new(),
+        };
+        let render = editor.refresh();
+        Ok((editor, render))
+    }
+
+    pub fn update(&mut self, message: EditorMessage) -> Task<Message> {
+        match message {
+            EditorMessage::Press(point) => {
+                self.selected = self
+                    .items
+                    .iter()
+                    .rev()
+                    .find(|(_, bounds)| bounds.contains(point.x, point.y))
+                    .map(|(item, _)| *item);
+                self.drag = self.selected.map(|_| Drag {
+                    start: point,
+                    offset: Vector::ZERO,
+                });
+                self.load_fields();
+            }
+            EditorMessage::DragTo(point) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.offset = point - drag.start;
+                }
+            }
+            EditorMessage::Release => {
+                if let (Some(drag), Some(item)) = (self.drag.take(), self.selected) {
+                    let (dx, dy) = (drag.offset.x.round() as i64, drag.offset.y.round() as i64);
+                    if dx != 0 || dy != 0 {
+                        let object = self.object_mut(item);
+                        for (key, delta) in [("x", dx), ("y", dy)] {
+                            let value = object.get(key).and_then(Value::as_i64).unwrap_or(0);
+                            object.insert(String::from(key), Value::from(value + delta));
+                        }
+                        self.load_fields();
+                        return self.refresh();
+                    }
+                }
+            }
+            EditorMessage::Field(key, value) => {
+                if let Some((_, field)) = self.fields.iter_mut().find(|(name, _)| *name == key) {
+                    field.clone_from(&value);
+                }
+                match self.set_field(key, &value) {
+                    Ok(()) => return self.refresh(),
+                    Err(e) => self.message = e,
+                }
+            }
+            // The template changed again while this was rendering.
+            EditorMessage::Rendered(revision, _) if revision != self.revision => {}
+            EditorMessage::Rendered(_, result) => match result {
+                Ok((preview, size, items, assets)) => {
+                    self.preview = Some(preview);
+                    self.canvas_size = size;
+                    self.items = items;
+                    self.assets = Some(assets);
+                    self.message.clear();
+                }
+                // Keep the old preview while the template is invalid.
+                Err(e) => self.message = e,
+            },
+        }
+        Task::none()
+    }
+
+    /// Writes the template back to its file, keeping the original field order. A template that
+    /// wouldn't load is refused, as it would be left out the next time the app starts.
+    pub fn save(&self) -> Result<(), String> {
+        img::validate_template(&self.document, &self.path)
+            .map_err(|e| format!("Not saved: {e}"))?;
+        let mut data = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        self.document
+            .serialize(&mut serde_json::Serializer::with_formatter(
+                &mut data, formatter,
+            ))
+            .map_err(|e| format!("Could not write the template: {e}"))?;
+        fs::write(&self.path, data)
+            .map_err(|e| format!("Failed to write {}: {e}", self.path.display()))
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Re-renders the preview after a change in the background.
+    fn refresh(&mut self) -> Task<Message> {
+        self.revision = NEXT_REVISION.fetch_add(1, Ordering::Relaxed);
+        let (revision, document, info) = (self.revision, self.document.clone(), self.info.clone());
+        let assets = self.assets.clone();
+        Task::future(async move {
+            let preview = task::spawn_blocking(move || {
+                let (img, items, assets) = img::preview(&document, &info, assets)?;
+                let size = Size::new(img.width() as f32, img.height() as f32);
+                Ok((
+                    Handle::from_rgba(img.width(), img.height(), img.into_raw()),
+                    size,
+                    items,
+                    assets,
+                ))
+            })
+            .await
+            .unwrap();
+            Message::Editor(EditorMessage::Rendered(revision, preview))
+        })
+    }
+
+    fn field_kinds(item: LayoutItem) -> &'static [(&'static str, Kind)] {
+        match item {
+            LayoutItem::Text(_) => &TEXT_FIELDS,
+            LayoutItem::Background(_) | LayoutItem::Foreground(_) => &LAYER_FIELDS,
+        }
+    }
+
+    fn load_fields(&mut self) {
+        self.fields = match self.selected {
+            Some(item) => Editor::field_kinds(item)
+                .iter()
+                .map(|(key, _)| (*key, self.field_text(item, key)))
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    fn entry(&self, item: LayoutItem) -> Option<&Value> {
+        let (list, i) = item_location(item);
+        self.document.get(list)?.get(i)
+    }
+
+    fn field_text(&self, item: LayoutItem, key: &str) -> String {
+        match self.entry(item) {
+            // Layers can be written as just the path.
+            Some(Value::String(path)) if key == "path" => path.clone(),
+            Some(Value::Object(object)) => match object.get(key) {
+                Some(Value::String(text)) => text.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    /// The JSON object for `item`, turning a layer written as a plain path into an object.
+    fn object_mut(&mut self, item: LayoutItem) -> &mut Map<String, Value> {
+        let (list, i) = item_location(item);
+        let entry = &mut self.document[list][i];
+        if let Value::String(path) = entry {
+            let path = std::mem::take(path);
+            *entry = Value::Object(Map::from_iter([(String::from("path"), Value::from(path))]));
+        }
+        entry
+            .as_object_mut()
+            .expect("Template entries are strings or objects")
+    }
+
+    fn set_field(&mut self, key: &str, text: &str) -> Result<(), String> {
+        let item = self.selected.ok_or("Nothing is selected")?;
+        let kind = Editor::field_kinds(item)
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| format!("Unknown property \"{key}\""))?;
+        let trimmed = text.trim();
+        let optional = matches!(
+            kind,
+            Kind::OptionalText | Kind::OptionalInteger | Kind::OptionalNumber
+        );
+        let value = match kind {
+            _ if optional && trimmed.is_empty() => None,
+            Kind::Text => Some(Value::from(text)),
+            Kind::OptionalText => Some(Value::from(trimmed)),
+            Kind::Integer | Kind::OptionalInteger => Some(Value::from(
+                trimmed
+                    .parse::<i64>()
+                    .map_err(|_| format!("{key} must be a whole number"))?,
+            )),
+            Kind::Number | Kind::OptionalNumber => Some(Value::Number(
+                trimmed
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .ok_or_else(|| format!("{key} must be a number"))?,
+            )),
+        };
+        let object = self.object_mut(item);
+        match value {
+            Some(value) => {
+                object.insert(String::from(key), value);
+            }
+            None => {
+                object.shift_remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let mut panel = Column::new().spacing(5);
+        match self.selected {
+            Some(item) => {
+                panel = panel.push(Text::new(item_name(item)).size(20));
+                for (key, value) in &self.fields {
+                    let key = *key;
+                    panel = panel.push(
+                        Row::new()
+                            .spacing(5)
+                            .align_y(iced::alignment::Vertical::Center)
+                            .push(Text::new(key).width(iced::Length::FillPortion(1)))
+                            .push(
+                                TextInput::new("", value)
+                                    .width(iced::Length::FillPortion(2))
+                                    .on_input(move |value| {
+                                        Message::Editor(EditorMessage::Field(key, value))
+                                    }),
+                            ),
+                    );
+                }
+            }
+            None => {
+                panel = panel.push(Text::new(
+                    "Click a text or image to select it, and drag it to move it.",
+                ));
+            }
+        }
+        Column::new()
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .padding(10)
+                    .height(iced::Length::Fill)
+                    .push(
+                        Canvas::new(LayoutCanvas { editor: self })
+                            .width(iced::Length::FillPortion(3))
+                            .height(iced::Length::Fill),
+                    )
+                    .push(panel.width(iced::Length::FillPortion(1))),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_y(iced::alignment::Vertical::Center)
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(
+                        Button::new(Text::new("Save"))
+                            .width(100.0)
+                            .on_press(Message::SaveTemplate),
+                    )
+                    .push(
+                        Text::new(&self.message)
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Center),
+                    )
+                    .push(
+                        Button::new(Text::new("Close Editor"))
+                            .width(200.0)
+                            .on_press(Message::CloseEditor),
+                    ),
+            )
+            .into()
+    }
+
+    /// Where the thumbnail is drawn within a canvas widget of `size`, and at what scale.
+    fn viewport(&self, size: Size) -> (Vector, f32) {
+        if self.canvas_size.width <= 0.0 || self.canvas_size.height <= 0.0 {
+            return (Vector::ZERO, 1.0);
+        }
+        let scale =
+            (size.width / self.canvas_size.width).min(size.height / self.canvas_size.height);
+        let origin = Vector::new(
+            (size.width - self.canvas_size.width * scale) / 2.0,
+            (size.height - self.canvas_size.height * scale) / 2.0,
+        );
+        (origin, scale)
+    }
+}
+
+fn item_location(item: LayoutItem) -> (&'static str, usize) {
+    match item {
+        LayoutItem::Background(i) => ("background_images", i),
+        LayoutItem::Foreground(i) => ("foreground_images", i),
+        LayoutItem::Text(i) => ("positioned_texts", i),
+    }
+}
+
+fn item_name(item: LayoutItem) -> String {
+    match item {
+        LayoutItem::Background(i) => format!("Background Image {}", i + 1),
+        LayoutItem::Foreground(i) => format!("Foreground Image {}", i + 1),
+        LayoutItem::Text(i) => format!("Text {}", i + 1),
+    }
+}
+
+/// Fills empty entries with sample values so every text has something to grab.
+fn with_placeholders(mut info: SetInfo) -> SetInfo {
+    for (field, placeholder) in [
+        (&mut info.tournament_name, "Tournament"),
+        (&mut info.round_name, "Round"),
+        (&mut info.date, "2025-01-01"),
+        (&mut info.player_1, "Player 1"),
+        (&mut info.player_2, "Player 2"),
+        (&mut info.score_1, "0"),
+        (&mut info.score_2, "0"),
+    ] {
+        if field.trim().is_empty() {
+            *field = String::from(placeholder);
+        }
+    }
+    info
+}
+
+struct LayoutCanvas<'a> {
+    editor: &'a Editor,
+}
+
+impl Program<Message> for LayoutCanvas<'_> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut (),
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let (origin, scale) = self.editor.viewport(bounds.size());
+        let to_template = |position: Point| {
+            let local = position - bounds.position() - origin;
+            Point::new(local.x / scale, local.y / scale)
+        };
+        let message = match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => cursor
+                .position_over(bounds)
+                .map(|position| EditorMessage::Press(to_template(position))),
+            Event::Mouse(mouse::Event::CursorMoved { position }) if self.editor.drag.is_some() => {
+                Some(EditorMessage::DragTo(to_template(position)))
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if self.editor.drag.is_some() =>
+            {
+                Some(EditorMessage::Release)
+            }
+            _ => None,
+        };
+        match message {
+            Some(message) => (event::Status::Captured, Some(Message::Editor(message))),
+            None => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let (origin, scale) = self.editor.viewport(bounds.size());
+        if let Some(preview) = &self.editor.preview {
+            frame.draw_image(
+                Rectangle::new(Point::ORIGIN + origin, self.editor.canvas_size * scale),
+                preview,
+            );
+        }
+        let selected = self
+            .editor
+            .items
+            .iter()
+            .find(|(item, _)| Some(*item) == self.editor.selected);
+        if let Some((_, item_bounds)) = selected {
+            let offset = self.editor.drag.map_or(Vector::ZERO, |drag| drag.offset);
+            // Turned and slanted text gets a matching outline rather than an upright box.
+            let [first, rest @ ..] = item_bounds.corners().map(|(x, y)| {
+                let corner = Point::new(x, y) + offset;
+                Point::new(corner.x * scale, corner.y * scale) + origin
+            });
+            let outline = Path::new(|path| {
+                path.move_to(first);
+                rest.into_iter().for_each(|corner| path.line_to(corner));
+                path.close();
+            });
+            frame.stroke(
+                &outline,
+                Stroke::default()
+                    .with_color(Color::from_rgb(1.0, 0.8, 0.0))
+                    .with_width(2.0),
+            );
+        }
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        _state: &(),
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if self.editor.drag.is_some() {
+            mouse::Interaction::Grabbing
+        } else if cursor.is_over(bounds) {
+            mouse::Interaction::Crosshair
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use image::imageops::colorops::{brighten_in_place, contrast_in_place, huerotate_in_place};
+use image::imageops::grayscale_alpha;
+use image::{DynamicImage, Rgba, RgbaImage};
+use imageproc::filter::gaussian_blur_f32;
+use serde::Deserialize;
+
+use crate::img::{format_color, parse_color, resolve_color, validate_color};
+use crate::template::SetInfo;
+
+/// A filter applied to an image layer before it is composited.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Effect {
+    /// Gaussian blur with standard deviation `sigma`, in pixels.
+    Blur {
+        sigma: f32,
+    },
+    /// Added to every color channel, from -255 to 255.
+    Brightness {
+        value: i32,
+    },
+    /// Contrast change in percent; negative values reduce contrast.
+    Contrast {
+        value: f32,
+    },
+    Grayscale,
+    HueRotate {
+        degrees: i32,
+    },
+    /// Mixes every pixel towards `color` by `amount` (0.0 to 1.0).
+    Tint {
+        color: String,
+        #[serde(default = "full_amount")]
+        amount: f32,
+    },
+    /// Replaces the color of every pixel, keeping the layer's shape.
+    Fill {
+        color: String,
+    },
+    /// Like `fill`, but with a linear gradient.
+    Gradient(Gradient),
+}
+
+fn full_amount() -> f32 {
+    1.0
+}
+
+/// A linear gradient across a whole layer, or across a text's bounding box.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Gradient {
+    /// Direction of the gradient in degrees: 0 runs left to right, 90 runs top to bottom.
+    #[serde(default)]
+    pub angle: f32,
+    pub stops: Vec<GradientStop>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GradientStop {
+    /// Position along the gradient, from 0.0 to 1.0.
+    pub offset: f32,
+    pub color: String,
+}
+
+impl Effect {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Effect::Blur { sigma } if *sigma <= 0.0 => {
+                Err(String::from("Blur sigma must be greater than 0"))
+            }
+            Effect::Tint { color, .. } | Effect::Fill { color } => validate_color(color),
+            Effect::Gradient(gradient) => gradient.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, img: RgbaImage, info: &SetInfo) -> Result<RgbaImage, String> {
+        let mut img = img;
+        match self {
+            Effect::Blur { sigma } => return Ok(gaussian_blur_f32(&img, *sigma)),
+            Effect::Brightness { value } => brighten_in_place(&mut img, *value),
+            Effect::Contrast { value } => {
+                // `contrast_in_place` stretches alpha too, which would change the layer's shape.
+                let alpha: Vec<u8> = img.pixels().map(|pixel| pixel.0[3]).collect();
+                contrast_in_place(&mut img, *value);
+                img.pixels_mut()
+                    .zip(alpha)
+                    .for_each(|(pixel, alpha)| pixel.0[3] = alpha);
+            }
+            Effect::Grayscale => return Ok(DynamicImage::from(grayscale_alpha(&img)).to_rgba8()),
+            Effect::HueRotate { degrees } => huerotate_in_place(&mut img, *degrees),
+            Effect::Tint { color, amount } => {
+                let color = resolve_color(color, info)?;
+                let amount = amount.clamp(0.0, 1.0);
+                img.pixels_mut().for_each(|pixel| {
+                    for (channel, target) in pixel.0[..3].iter_mut().zip(color.0) {
+                        *channel = (*channel as f32 * (1.0 - amount) + target as f32 * amount)
+                            .round() as u8;
+                    }
+                });
+            }
+            Effect::Fill { color } => {
+                let color = resolve_color(color, info)?;
+                img.pixels_mut()
+                    .for_each(|pixel| *pixel = recolor(*pixel, color));
+            }
+            Effect::Gradient(gradient) => {
+                let sampler = gradient
+                    .resolved(info)?
+                    .sampler(img.width(), img.height())?;
+                img.enumerate_pixels_mut()
+                    .for_each(|(x, y, pixel)| *pixel = recolor(*pixel, sampler(x, y)));
+            }
+        }
+        Ok(img)
+    }
+}
+
+/// Applies `effects` in order.
+pub fn apply_effects(
+    img: RgbaImage,
+    effects: &[Effect],
+    info: &SetInfo,
+) -> Result<RgbaImage, String> {
+    effects
+        .iter()
+        .try_fold(img, |img, effect| effect.apply(img, info))
+}
+
+/// Takes the color from `color` and the shape from `pixel`'s alpha.
+fn recolor(pixel: Rgba<u8>, color: Rgba<u8>) -> Rgba<u8> {
+    let alpha = (pixel.0[3] as u16 * color.0[3] as u16 / 255) as u8;
+    Rgba([color.0[0], color.0[1], color.0[2], alpha])
+}
+
+impl Gradient {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stops.is_empty() {
+            return Err(String::from("Gradients need at least one stop"));
+        }
+        self.stops
+            .iter()
+            .try_for_each(|stop| validate_color(&stop.color))
+    }
+
+    /// A copy with any tokens in the stop colors filled in.
+    pub fn resolved(&self, info: &SetInfo) -> Result<Gradient, String> {
+        let mut gradient = self.clone();
+        for stop in &mut gradient.stops {
+            stop.color = format_color(resolve_color(&stop.color, info)?);
+        }
+        Ok(gradient)
+    }
+
+    /// Returns a function giving the gradient's color at each pixel of a `width` x `height` area.
+    pub fn sampler(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<impl Fn(u32, u32) -> Rgba<u8>, String> {
+        let mut stops = self
+            .stops
+            .iter()
+            .map(|stop| Ok((stop.offset.clamp(0.0, 1.0), parse_color(&stop.color)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (dx, dy) = (self.angle.to_radians().cos(), self.angle.to_radians().sin());
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let extent = (cx * dx.abs() + cy * dy.abs()).max(f32::EPSILON);
+        Ok(move |x: u32, y: u32| {
+            let along = (x as f32 + 0.5 - cx) * dx + (y as f32 + 0.5 - cy) * dy;
+            color_at(&stops, (along / extent + 1.0) / 2.0)
+        })
+    }
+}
+
+fn color_at(stops: &[(f32, Rgba<u8>)], t: f32) -> Rgba<u8> {
+    let first = stops[0];
+    let last = stops[stops.len() - 1];
+    if t <= first.0 {
+        return first.1;
+    }
+    if t >= last.0 {
+        return last.1;
+    }
+    let i = stops.windows(2).position(|w| t <= w[1].0).unwrap_or(0);
+    let ((start, from), (end, to)) = (stops[i], stops[i + 1]);
+    let mix = if end > start {
+        (t - start) / (end - start)
+    } else {
+        1.0
+    };
+    Rgba(std::array::from_fn(|c| {
+        (from.0[c] as f32 + (to.0[c] as f32 - from.0[c] as f32) * mix).round() as u8
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    fn apply(effect: Effect, pixel: Rgba<u8>) -> [u8; 4] {
+        let img = RgbaImage::from_pixel(1, 1, pixel);
+        effect
+            .apply(img, &SetInfo::default())
+            .unwrap()
+            .get_pixel(0, 0)
+            .0
+    }
+
+    fn gradient(angle: f32) -> Gradient {
+        let stop = |offset, color: &str| GradientStop {
+            offset,
+            color: String::from(color),
+        };
+        Gradient {
+            angle,
+            stops: vec![stop(0.0, "#FF0000"), stop(1.0, "#0000FF")],
+        }
+    }
+
+    #[test]
+    fn color_at_the_stops_is_the_stop_color() {
+        let stops = [(0.2, RED), (0.8, BLUE)];
+        assert_eq!(color_at(&stops, 0.2), RED);
+        assert_eq!(color_at(&stops, 0.8), BLUE);
+    }
+
+    #[test]
+    fn color_past_the_end_stops_is_held() {
+        let stops = [(0.2, RED), (0.8, BLUE)];
+        assert_eq!(color_at(&stops, 0.0), RED);
+        assert_eq!(color_at(&stops, 1.0), BLUE);
+    }
+
+    #[test]
+    fn color_between_stops_is_mixed() {
+        let stops = [(0.0, RED), (1.0, BLUE)];
+        assert_eq!(color_at(&stops, 0.5).0, [128, 0, 128, 255]);
+        let stops = [(0.0, RED), (0.5, BLUE), (1.0, Rgba([0, 0, 0, 0]))];
+        assert_eq!(color_at(&stops, 0.75).0, [0, 0, 128, 128]);
+    }
+
+    #[test]
+    fn zero_degrees_runs_left_to_right() {
+        let sampler = gradient(0.0).sampler(10, 10).unwrap();
+        assert_eq!(sampler(0, 5).0, [242, 0, 13, 255]);
+        assert_eq!(sampler(9, 5).0, [13, 0, 242, 255]);
+        assert_eq!(sampler(0, 0), sampler(0, 9));
+    }
+
+    #[test]
+    fn ninety_degrees_runs_top_to_bottom() {
+        let sampler = gradient(90.0).sampler(10, 10).unwrap();
+        assert_eq!(sampler(5, 0).0, [242, 0, 13, 255]);
+        assert_eq!(sampler(5, 9).0, [13, 0, 242, 255]);
+        assert_eq!(sampler(0, 0), sampler(9, 0));
+    }
+
+    #[test]
+    fn reversed_angle_reverses_the_gradient() {
+        let forward = gradient(0.0).sampler(10, 1).unwrap();
+        let backward = gradient(180.0).sampler(10, 1).unwrap();
+        assert_eq!(forward(0, 0), backward(9, 0));
+    }
+
+    #[test]
+    fn tint_mixes_the_color_and_keeps_alpha() {
+        let tint = Effect::Tint {
+            color: String::from("#FF0000"),
+            amount: 0.5,
+        };
+        assert_eq!(apply(tint, Rgba([0, 100, 200, 77])), [128, 50, 100, 77]);
+    }
+
+    #[test]
+    fn fill_keeps_the_shape() {
+        let fill = Effect::Fill {
+            color: String::from("#00FF0080"),
+        };
+        assert_eq!(apply(fill, Rgba([10, 20, 30, 255])), [0, 255, 0, 128]);
+        let fill = Effect::Fill {
+            color: String::from("#00FF00"),
+        };
+        assert_eq!(apply(fill, Rgba([10, 20, 30, 0])), [0, 255, 0, 0]);
+    }
+
+    #[test]
+    fn brightness_shifts_the_colors() {
+        let effect = Effect::Brightness { value: 20 };
+        assert_eq!(apply(effect, Rgba([100, 250, 0, 128])), [120, 255, 20, 128]);
+        let effect = Effect::Brightness { value: -20 };
+        assert_eq!(apply(effect, Rgba([100, 250, 10, 128])), [80, 230, 0, 128]);
+    }
+
+    #[test]
+    fn contrast_pushes_colors_away_from_grey() {
+        let effect = Effect::Contrast { value: 100.0 };
+        assert_eq!(apply(effect, Rgba([64, 192, 64, 200])), [0, 255, 0, 200]);
+    }
+}
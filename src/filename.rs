@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use crate::template::{self, SetInfo};
+
+/// Used when a template doesn't set `filename`.
+pub const DEFAULT_FILENAME: &str = "{tournament} - {round|suffix: - }{player1} vs {player2}";
+
+/// Leaves room for the extension and a collision suffix within the usual 255 byte limit.
+const MAX_STEM_BYTES: usize = 200;
+
+#[cfg(windows)]
+const ILLEGAL_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+#[cfg(not(windows))]
+const ILLEGAL_CHARS: &[char] = &['/'];
+
+#[cfg(windows)]
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+#[cfg(not(windows))]
+const RESERVED_NAMES: &[&str] = &[];
+
+/// Builds an output filename (without extension) from `pattern`.
+///
+/// The name is made safe for the current platform, and suffixed with " (2)", " (3)", ...
+/// if the name followed by any of `suffixes` (e.g. ".jpg") already exists in `output_folder`.
+pub fn get_filename(
+    output_folder: &Path,
+    pattern: &str,
+    info: &SetInfo,
+    suffixes: &[String],
+) -> Result<String, String> {
+    let stem = sanitize(&template::render(pattern, info)?);
+    let taken = |stem: &str| {
+        suffixes
+            .iter()
+            .any(|suffix| output_folder.join(format!("{stem}{suffix}")).exists())
+    };
+    if !taken(&stem) {
+        return Ok(stem);
+    }
+    (2..)
+        .map(|n| format!("{stem} ({n})"))
+        .find(|candidate| !taken(candidate))
+        .ok_or_else(|| String::from("Could not find an unused filename"))
+}
+
+/// Replaces characters that can't appear in a filename and caps the length.
+pub fn sanitize(name: &str) -> String {
+    let mut clean: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || ILLEGAL_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    if clean.len() > MAX_STEM_BYTES {
+        let mut end = MAX_STEM_BYTES;
+        while !clean.is_char_boundary(end) {
+            end -= 1;
+        }
+        clean.truncate(end);
+    }
+    // Windows silently drops trailing dots and spaces, so don't rely on them.
+    let mut clean = clean.trim_end_matches(['.', ' ']).trim_start().to_string();
+    if clean.is_empty() {
+        clean = String::from("untitled");
+    }
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(&clean))
+    {
+        clean.push('_');
+    }
+    clean
+}
@@ -0,0 +1,182 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use iced::widget::image::Handle;
+use iced::widget::{button, image, scrollable, Button, Column, Row, Text};
+use iced::Element;
+
+use crate::timeline::Timeline;
+use crate::video::{extract_tiles, format_timestamp, FFMPEG_PATH};
+use crate::Message;
+
+/// Tiles are kept here between runs, in a folder per input file and interval.
+const CACHE_DIR: &str = "static/filmstrip";
+/// Filmstrips kept at most, the oldest going first.
+const MAX_CACHED: usize = 20;
+const TILE_WIDTH: u32 = 160;
+const TILE_HEIGHT: f32 = 90.0;
+/// How much of the start and end of the input goes into its hash.
+const HASH_SAMPLE: u64 = 1 << 20;
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Low resolution frames sampled across the whole input, for finding sets at a glance.
+#[derive(Clone, Debug)]
+pub struct Filmstrip {
+    /// Seconds between tiles.
+    interval: u32,
+    /// Each tile and its time on the timeline.
+    tiles: Vec<(f64, Handle)>,
+}
+
+impl Filmstrip {
+    /// Loads the tiles of every input file from the cache, extracting them first if needed.
+    pub fn build(timeline: &Timeline, interval: u32) -> Result<Filmstrip, String> {
+        if interval == 0 {
+            return Err(String::from("Filmstrip interval must be at least 1 second"));
+        }
+        let mut tiles = Vec::new();
+        for (input, offset) in timeline.files() {
+            let paths = tile_paths(input, interval)?;
+            tiles.extend(paths.into_iter().enumerate().map(|(index, path)| {
+                (
+                    offset + index as f64 * interval as f64,
+                    Handle::from_path(path),
+                )
+            }));
+        }
+        Ok(Filmstrip { interval, tiles })
+    }
+
+    /// Shows the tiles in a horizontally scrolling strip, highlighting the one nearest `position`.
+    pub fn view(&self, position: Option<f64>) -> Element<'_, Message> {
+        let selected = position.and_then(|position| {
+            self.tiles
+                .iter()
+                .rposition(|(time, _)| *time <= position + self.interval as f64 / 2.0)
+        });
+        let tiles = self.tiles.iter().enumerate().map(|(index, (time, tile))| {
+            Button::new(
+                Column::new()
+                    .align_x(iced::alignment::Horizontal::Center)
+                    .push(image(tile.clone()).height(TILE_HEIGHT))
+                    .push(Text::new(format_timestamp(*time)).size(12)),
+            )
+            .padding(2)
+            .style(if selected == Some(index) {
+                button::primary
+            } else {
+                button::text
+            })
+            .on_press(Message::JumpTo(*time))
+            .into()
+        });
+        scrollable(
+            Row::with_children(tiles)
+                .spacing(2)
+                .padding(iced::Padding::new(0.0).bottom(10.0)),
+        )
+        .direction(scrollable::Direction::Horizontal(
+            scrollable::Scrollbar::new(),
+        ))
+        .into()
+    }
+}
+
+/// The cached tiles of `input` in order, extracting them first if needed.
+fn tile_paths(input: &str, interval: u32) -> Result<Vec<PathBuf>, String> {
+    let prefix = format!("{:016x}_", path_hash(input));
+    let dir = Path::new(CACHE_DIR).join(format!("{prefix}{:016x}_{interval}", file_hash(input)?));
+    if !dir.exists() {
+        // Extract into a separate folder so an interrupted run isn't mistaken for a finished one.
+        let partial = dir.with_extension("partial");
+        if partial.exists() {
+            fs::remove_dir_all(&partial)
+                .map_err(|e| format!("Failed to clear {}: {e}", partial.display()))?;
+        }
+        fs::create_dir_all(&partial)
+            .map_err(|e| format!("Failed to create {}: {e}", partial.display()))?;
+        extract_tiles(FFMPEG_PATH, input, interval, TILE_WIDTH, &partial)?;
+        fs::rename(&partial, &dir)
+            .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        prune(&dir, &prefix);
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read {}: {e}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jpg"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Removes the other filmstrips of the input whose folders start with `prefix`, as they're for
+/// an older version of it or another interval, then the oldest filmstrips past `MAX_CACHED`.
+fn prune(keep: &Path, prefix: &str) {
+    let Ok(entries) = fs::read_dir(CACHE_DIR) else {
+        return;
+    };
+    let mut others: Vec<(std::time::SystemTime, PathBuf)> = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Another build may still be extracting into a partial folder.
+        if path == keep || !path.is_dir() || name.ends_with(".partial") {
+            continue;
+        }
+        if name.starts_with(prefix) {
+            let _ = fs::remove_dir_all(&path);
+            continue;
+        }
+        let modified = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(UNIX_EPOCH);
+        others.push((modified, path));
+    }
+    others.sort();
+    let excess = (others.len() + 1).saturating_sub(MAX_CACHED);
+    for (_, path) in others.into_iter().take(excess) {
+        let _ = fs::remove_dir_all(path);
+    }
+}
+
+/// Identifies an input by where it is, so newer filmstrips of it can replace older ones.
+fn path_hash(input: &str) -> u64 {
+    let path = fs::canonicalize(input).unwrap_or_else(|_| PathBuf::from(input));
+    fnv1a(FNV_OFFSET_BASIS, path.to_string_lossy().as_bytes())
+}
+
+/// Identifies a file by its size, modification time and the bytes at its start and end, which is
+/// much quicker than hashing a multi-hour recording in full.
+fn file_hash(input: &str) -> Result<u64, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read {input}: {e}");
+    let mut file = File::open(input).map_err(read_error)?;
+    let metadata = file.metadata().map_err(read_error)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos());
+    let mut sample = Vec::new();
+    (&mut file)
+        .take(HASH_SAMPLE)
+        .read_to_end(&mut sample)
+        .map_err(read_error)?;
+    file.seek(SeekFrom::Start(metadata.len().saturating_sub(HASH_SAMPLE)))
+        .map_err(read_error)?;
+    file.read_to_end(&mut sample).map_err(read_error)?;
+    let hash = fnv1a(FNV_OFFSET_BASIS, &metadata.len().to_le_bytes());
+    let hash = fnv1a(hash, &modified.to_le_bytes());
+    Ok(fnv1a(hash, &sample))
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, it gives the same hash on every Rust version, so the
+/// cache survives toolchain updates.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
@@ -1,14 +1,30 @@
 use ab_glyph::{FontRef, PxScale};
 use cached::proc_macro::cached;
+use cached::Cached;
 use image::buffer::ConvertBuffer;
-use image::imageops::overlay;
-use image::{open, ImageBuffer, Rgb, Rgba, RgbaImage};
-use imageproc::drawing::{draw_text_mut, text_size};
-use imageproc::geometric_transformations::{rotate, Interpolation};
-use serde::Deserialize;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{blur, crop_imm, grayscale_alpha, overlay, resize, FilterType};
+use image::{open, DynamicImage, ImageBuffer, Rgb, Rgba, RgbaImage};
+use imageproc::geometric_transformations::{warp, Interpolation, Projection};
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::{LazyLock, RwLock};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+
+use crate::blend::{composite, BlendMode};
+use crate::characters::{self, CharacterMetadata};
+use crate::condition;
+use crate::effects::{apply_effects, Effect, Gradient};
+use crate::filename::{sanitize, DEFAULT_FILENAME};
+use crate::settings::{load_settings, save_settings};
+use crate::template::{self, SetInfo};
+use crate::text::{Align, TextFill, TextStyle, TextTransform};
+use crate::video::VideoSettings;
 
 #[derive(Deserialize, Clone)]
 pub struct Config {
@@ -17,204 +33,1238 @@ pub struct Config {
     base_path: String,
     char_img_path: String,
     font: String,
-    background_images: Vec<String>,
-    foreground_images: Vec<String>,
+    #[serde(deserialize_with = "deserialize_layers")]
+    background_images: Vec<ImageLayer>,
+    #[serde(deserialize_with = "deserialize_layers")]
+    foreground_images: Vec<ImageLayer>,
     positioned_texts: Vec<PositionedText>,
+    #[serde(default)]
+    grey_out_loser: bool,
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    output: OutputSettings,
+    #[serde(default)]
+    renditions: Vec<Rendition>,
+    #[serde(default)]
+    video: VideoSettings,
+    #[serde(skip)]
+    assets: Arc<Assets>,
+    /// The file the template was loaded from.
+    #[serde(skip)]
+    source: PathBuf,
+}
+
+/// What a template loads from disk besides its layout: its font and character images.
+#[derive(Default)]
+pub struct Assets {
+    /// The `base_path`, `font` and `char_img_path` these were loaded from.
+    source: (String, String, String),
+    font_data: Vec<u8>,
+    characters: Vec<String>,
+    character_metadata: HashMap<String, CharacterMetadata>,
+}
+
+impl fmt::Debug for Assets {
+    // Leaves out the font, which runs to megabytes.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Assets")
+            .field("source", &self.source)
+            .field("characters", &self.characters.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ImageLayer {
+    #[serde(default)]
+    path: String,
+    /// Use a frame from the input video instead of the image at `path`.
+    #[serde(default)]
+    vod_frame: Option<VodFrame>,
+    #[serde(default)]
+    effects: Vec<Effect>,
+    #[serde(default)]
+    blend: BlendMode,
+    /// Offset of the image's top left corner from the top left of the canvas, in pixels.
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+    #[serde(flatten)]
+    conditions: Conditions,
 }
 
+/// Options for a layer showing a frame grabbed from the input video.
 #[derive(Deserialize, Clone)]
+pub struct VodFrame {
+    #[serde(default)]
+    fit: Fit,
+    /// Gaussian blur radius, in pixels.
+    #[serde(default)]
+    blur: f32,
+    /// How much to darken the frame, from 0.0 (unchanged) to 1.0 (black).
+    #[serde(default)]
+    darken: f32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
 pub struct PositionedText {
     text: String,
     x: i32,
     y: i32,
     scale: f32,
     theta: f32,
+    #[serde(default)]
+    color: Option<String>,
+    /// Fills the text with a gradient instead of `color`.
+    #[serde(default)]
+    gradient: Option<Gradient>,
+    /// One color per visible character, overriding `color` and `gradient` for those characters.
+    #[serde(default)]
+    char_colors: Vec<String>,
+    /// Extra space between characters, in pixels.
+    #[serde(default)]
+    letter_spacing: f32,
+    #[serde(default)]
+    transform: Option<TextTransform>,
+    /// Slant in degrees; positive values lean the text to the right like italics.
+    #[serde(default)]
+    skew_x: f32,
+    /// Wrap lines longer than this many pixels between words.
+    #[serde(default)]
+    max_width: Option<u32>,
+    /// Distance between lines as a multiple of `scale`.
+    #[serde(default = "default_line_height")]
+    line_height: f32,
+    /// Alignment of each line within a multi-line text.
+    #[serde(default)]
+    align: Align,
+    #[serde(flatten)]
+    conditions: Conditions,
 }
 
-pub static CONFIG: LazyLock<RwLock<Config>> = LazyLock::new(|| {
-    let config = load_config_from_file();
-    RwLock::new(config)
+fn default_line_height() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] = [OutputFormat::Jpeg, OutputFormat::Png, OutputFormat::Webp];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Png => "PNG",
+            OutputFormat::Webp => "WebP",
+        })
+    }
+}
+
+/// How the finished thumbnail is encoded.
+#[derive(Deserialize, Clone, Debug)]
+pub struct OutputSettings {
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// JPEG quality, 1-100.
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    /// If set, JPEG quality is lowered until the file fits within this many bytes.
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+}
+
+impl OutputSettings {
+    /// PNG and WebP are written losslessly, so there's nothing to lower to meet a size limit.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_file_size.is_some() && self.format != OutputFormat::Jpeg {
+            return Err(format!(
+                "A max file size only works for JPEG, not {}",
+                self.format
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            format: OutputFormat::default(),
+            quality: default_quality(),
+            max_file_size: None,
+        }
+    }
+}
+
+fn default_quality() -> u8 {
+    90
+}
+
+/// An extra copy of the thumbnail at a different size, e.g. for Twitter or vertical video.
+#[derive(Deserialize, Clone)]
+pub struct Rendition {
+    /// Appended to the output filename as " - {name}".
+    name: String,
+    width: u32,
+    height: u32,
+    /// How the main thumbnail is scaled to this size when there are no `overrides`.
+    #[serde(default)]
+    fit: Fit,
+    /// Top-level config fields to replace for this rendition, e.g. `positioned_texts`.
+    #[serde(default)]
+    overrides: Option<Map<String, Value>>,
+    #[serde(skip)]
+    config: Option<Arc<Config>>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale to fill the canvas, cropping whatever sticks out.
+    #[default]
+    Cover,
+    /// Scale to fit inside the canvas, leaving transparent bars.
+    Contain,
+    /// Scale each axis independently to exactly fill the canvas.
+    Stretch,
+}
+
+/// Restricts a layer or text to sets with a particular result or metadata.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Conditions {
+    /// Only draw when this expression holds, e.g. `round contains "Grand"`.
+    #[serde(default)]
+    when: Option<String>,
+    /// Only draw when this side (1 or 2) won the set.
+    #[serde(default)]
+    winner: Option<u8>,
+    /// Only draw when this side (1 or 2) lost the set.
+    #[serde(default)]
+    loser: Option<u8>,
+}
+
+impl Conditions {
+    fn validate(&self) -> Result<(), String> {
+        self.when.as_deref().map_or(Ok(()), condition::validate)
+    }
+
+    fn matches(&self, info: &SetInfo) -> Result<bool, String> {
+        let when = match &self.when {
+            Some(when) => condition::evaluate(when, info)?,
+            None => true,
+        };
+        Ok(when
+            && (self.winner.is_none() || self.winner == info.winner())
+            && (self.loser.is_none() || self.loser == info.loser()))
+    }
+}
+
+const MIN_JPEG_QUALITY: u8 = 30;
+const JPEG_QUALITY_STEP: u8 = 5;
+
+const DEFAULT_TEXT_COLOR: Rgba<u8> = Rgba([227, 228, 229, 255]);
+
+const DEFAULT_TEMPLATE: &str = "default";
+const TEMPLATES_DIR: &str = "static/templates";
+
+/// Every available thumbnail template, keyed by name. Broken template files are left out so
+/// the app can still start; see `skipped_templates`.
+pub static TEMPLATES: LazyLock<RwLock<BTreeMap<String, Config>>> = LazyLock::new(|| {
+    let (templates, errors) = load_templates();
+    *SKIPPED_TEMPLATES
+        .lock()
+        .expect("SKIPPED_TEMPLATES poisoned") = errors;
+    RwLock::new(templates)
 });
 
-pub static CHAR_IMGS: LazyLock<RwLock<Vec<String>>> =
-    LazyLock::new(|| RwLock::new(load_image_filenames()));
+static SKIPPED_TEMPLATES: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
-static FONT_BYTES: LazyLock<RwLock<Vec<u8>>> = LazyLock::new(|| {
-    let config = get_config();
-    RwLock::new(
-        fs::read(Path::new(&config.base_path).join(&config.font)).expect("Could not load font!"),
+/// Why any template files were left out when the app started.
+pub fn skipped_templates() -> Vec<String> {
+    LazyLock::force(&TEMPLATES);
+    SKIPPED_TEMPLATES
+        .lock()
+        .expect("SKIPPED_TEMPLATES poisoned")
+        .clone()
+}
+
+/// Loads `static/config.json` as the "default" template, plus one template per
+/// `.json` file in `static/templates`, named after the file. Files that fail to load are
+/// left out, and their errors returned alongside the rest.
+fn load_templates() -> (BTreeMap<String, Config>, Vec<String>) {
+    let mut templates = BTreeMap::new();
+    let mut errors = Vec::new();
+    let config_path = Path::new("static/config.json");
+    if config_path.exists() {
+        match load_config_from_file(config_path) {
+            Ok(config) => {
+                templates.insert(String::from(DEFAULT_TEMPLATE), config);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if let Ok(entries) = fs::read_dir(TEMPLATES_DIR) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .expect("File has an extension so must have a stem")
+                .to_string_lossy()
+                .into_owned();
+            if templates.contains_key(&name) {
+                errors.push(format!("Duplicate template name \"{name}\""));
+                continue;
+            }
+            match load_config_from_file(&path) {
+                Ok(config) => {
+                    templates.insert(name, config);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+    if templates.is_empty() && errors.is_empty() {
+        errors.push(format!(
+            "No templates found in static/config.json or {TEMPLATES_DIR}"
+        ));
+    }
+    (templates, errors)
+}
+
+fn load_config_from_file(path: &Path) -> Result<Config, String> {
+    let filename = path.display();
+    let data = fs::read_to_string(path).map_err(|e| format!("Failed to read {filename}: {e}"))?;
+    let value: Value =
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse {filename}: {e}"))?;
+    config_from_document(value, path)
+}
+
+/// Checks that a template document would load, renditions included, before it is saved to
+/// `path`.
+pub fn validate_template(document: &Value, path: &Path) -> Result<(), String> {
+    config_from_document(document.clone(), path).map(|_| ())
+}
+
+fn config_from_document(value: Value, path: &Path) -> Result<Config, String> {
+    let filename = path.display();
+    let mut config = config_from_value(value.clone(), &filename.to_string())?;
+    config.source = path.to_path_buf();
+    for rendition in &mut config.renditions {
+        if let Some(overrides) = &rendition.overrides {
+            let mut merged = value.clone();
+            let fields = merged.as_object_mut().expect("Config parsed as an object");
+            fields.remove("renditions");
+            fields.extend(overrides.clone());
+            fields.insert(String::from("width"), rendition.width.into());
+            fields.insert(String::from("height"), rendition.height.into());
+            let name = format!("{filename} (rendition \"{}\")", rendition.name);
+            rendition.config = Some(Arc::new(config_from_value(merged, &name)?));
+        }
+    }
+    Ok(config)
+}
+
+fn config_from_value(value: Value, filename: &str) -> Result<Config, String> {
+    let mut config = layout_from_value(value, filename)?;
+    config.assets = Arc::new(load_assets(&config, filename)?);
+    Ok(config)
+}
+
+/// Parses and checks a template without loading its assets.
+fn layout_from_value(value: Value, filename: &str) -> Result<Config, String> {
+    let mut config: Config =
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse {filename}: {e}"))?;
+    for positioned_text in &mut config.positioned_texts {
+        if let Some(token) = template::legacy_token(&positioned_text.text) {
+            positioned_text.text = token.to_string();
+        }
+    }
+    config
+        .positioned_texts
+        .iter()
+        .try_for_each(|positioned_text| {
+            template::validate(&positioned_text.text)?;
+            positioned_text.conditions.validate()?;
+            positioned_text
+                .color
+                .as_deref()
+                .map_or(Ok(()), validate_color)?;
+            if let Some(gradient) = &positioned_text.gradient {
+                gradient.validate()?;
+            }
+            positioned_text
+                .char_colors
+                .iter()
+                .try_for_each(|color| validate_color(color))
+        })
+        .map_err(|e| format!("Error in {filename}: {e}"))?;
+    config
+        .background_images
+        .iter()
+        .chain(&config.foreground_images)
+        .try_for_each(|layer| {
+            if layer.path.is_empty() == layer.vod_frame.is_none() {
+                return Err(String::from(
+                    "Image layers need exactly one of \"path\" or \"vod_frame\"",
+                ));
+            }
+            layer.effects.iter().try_for_each(Effect::validate)?;
+            layer.conditions.validate()
+        })
+        .map_err(|e| format!("Error in {filename}: {e}"))?;
+    if let Some(pattern) = &config.filename {
+        template::validate(pattern).map_err(|e| format!("Error in {filename}: {e}"))?;
+    }
+    config
+        .output
+        .validate()
+        .map_err(|e| format!("Error in {filename}: {e}"))?;
+    config
+        .video
+        .metadata
+        .values()
+        .try_for_each(|value| template::validate(value))
+        .map_err(|e| format!("Error in {filename}: {e}"))?;
+    Ok(config)
+}
+
+fn assets_source(config: &Config) -> (String, String, String) {
+    (
+        config.base_path.clone(),
+        config.font.clone(),
+        config.char_img_path.clone(),
     )
-});
+}
+
+fn load_assets(config: &Config, filename: &str) -> Result<Assets, String> {
+    let font_data = fs::read(Path::new(&config.base_path).join(&config.font))
+        .map_err(|e| format!("Could not load font for {filename}: {e}"))?;
+    FontRef::try_from_slice(&font_data)
+        .map_err(|e| format!("Could not load font for {filename}: {e}"))?;
+    Ok(Assets {
+        source: assets_source(config),
+        font_data,
+        characters: load_image_filenames(config)?,
+        character_metadata: characters::load_metadata(
+            &Path::new(&config.base_path).join(&config.char_img_path),
+        )?,
+    })
+}
+
+/// Image layers may be written as a plain filename or as an object with a `path`.
+fn deserialize_layers<'de, D>(deserializer: D) -> Result<Vec<ImageLayer>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Entry {
+        Path(String),
+        Layer(ImageLayer),
+    }
+    Ok(Vec::<Entry>::deserialize(deserializer)?
+        .into_iter()
+        .map(|entry| match entry {
+            Entry::Path(path) => ImageLayer {
+                path,
+                vod_frame: None,
+                effects: Vec::new(),
+                blend: BlendMode::Normal,
+                x: 0,
+                y: 0,
+                conditions: Conditions::default(),
+            },
+            Entry::Layer(layer) => layer,
+        })
+        .collect())
+}
 
-fn load_config_from_file() -> Config {
-    let path = Path::new("static/config.json");
-    let data = fs::read_to_string(path).expect("Failed to read config.json");
-    serde_json::from_str(&data).expect("Failed to parse config.json")
+/// Parses `#RRGGBB` or `#RRGGBBAA`.
+pub fn parse_color(color: &str) -> Result<Rgba<u8>, String> {
+    let hex = color.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("Invalid color \"{color}\""))
+    };
+    match hex.len() {
+        6 => Ok(Rgba([channel(0)?, channel(2)?, channel(4)?, 255])),
+        8 => Ok(Rgba([channel(0)?, channel(2)?, channel(4)?, channel(6)?])),
+        _ => Err(format!("Invalid color \"{color}\"")),
+    }
 }
 
-fn load_image_filenames() -> Vec<String> {
-    let config = get_config();
-    fs::read_dir(Path::new(&config.base_path).join(&config.char_img_path))
-        .expect("Could not open char_img_path")
-        .flatten()
-        .map(|f| f.file_name().to_str().unwrap().to_string())
-        .collect()
+/// Formats `color` as `#RRGGBB`, or `#RRGGBBAA` if it isn't opaque.
+pub fn format_color(color: Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    if a == 255 {
+        format!("#{r:02X}{g:02X}{b:02X}")
+    } else {
+        format!("#{r:02X}{g:02X}{b:02X}{a:02X}")
+    }
+}
+
+/// Checks a color that may contain tokens such as `{fighter1.accent}`.
+pub fn validate_color(color: &str) -> Result<(), String> {
+    if color.contains('{') {
+        template::validate(color)
+    } else {
+        parse_color(color).map(|_| ())
+    }
+}
+
+/// Fills in any tokens in `color`, then parses it.
+pub fn resolve_color(color: &str, info: &SetInfo) -> Result<Rgba<u8>, String> {
+    parse_color(&template::render(color, info)?)
 }
 
-fn get_config() -> Config {
-    CONFIG
+fn load_image_filenames(config: &Config) -> Result<Vec<String>, String> {
+    let mut filenames: Vec<String> =
+        fs::read_dir(Path::new(&config.base_path).join(&config.char_img_path))
+            .map_err(|e| format!("Could not open char_img_path: {e}"))?
+            .flatten()
+            .map(|f| f.file_name().to_str().unwrap().to_string())
+            .filter(|filename| characters::is_image(filename))
+            .collect();
+    filenames.sort();
+    if filenames.is_empty() {
+        return Err(String::from("No character images found in char_img_path"));
+    }
+    Ok(filenames)
+}
+
+fn get_config(template: &str) -> Result<Config, String> {
+    TEMPLATES
         .read()
         .expect("RwLock poisoned during get_config()")
-        .clone()
+        .get(template)
+        .cloned()
+        .ok_or_else(|| format!("Unknown template \"{template}\""))
+}
+
+pub fn template_names() -> Vec<String> {
+    TEMPLATES
+        .read()
+        .expect("RwLock poisoned during template_names()")
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// The template chosen in settings.json if it exists, otherwise "default" or the first one.
+pub fn default_template() -> String {
+    let names = template_names();
+    load_settings()
+        .ok()
+        .and_then(|settings| settings.default_template)
+        .filter(|name| names.contains(name))
+        .or_else(|| names.iter().find(|name| *name == DEFAULT_TEMPLATE).cloned())
+        .or_else(|| names.first().cloned())
+        .unwrap_or_default()
+}
+
+pub fn set_default_template(template: &str) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    settings.default_template = Some(template.to_string());
+    save_settings(&settings)
 }
 
-pub fn reload_config() {
-    let new_config = load_config_from_file();
-    let mut conf_write_guard = CONFIG
+/// Character image filenames available to `template`.
+pub fn characters(template: &str) -> Vec<String> {
+    get_config(template)
+        .map(|config| config.assets.characters.clone())
+        .unwrap_or_default()
+}
+
+pub fn reload_config() -> Result<(), String> {
+    // Keep the templates already loaded rather than losing the broken one.
+    let (new_templates, errors) = load_templates();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let mut write_guard = TEMPLATES
         .write()
         .expect("RwLock poisoned during reload_config()");
-    let mut font_write_guard = FONT_BYTES
-        .write()
-        .expect("Font RwLock poisoned during reload_config()");
-    *font_write_guard = fs::read(Path::new(&new_config.base_path).join(&new_config.font))
-        .expect("Could not load font!");
-    *conf_write_guard = new_config;
+    *write_guard = new_templates;
+    DRAW_CENTERED_TEXT
+        .lock()
+        .expect("DRAW_CENTERED_TEXT poisoned")
+        .cache_clear();
+    LOAD_IMAGE
+        .lock()
+        .expect("LOAD_IMAGE poisoned")
+        .cache_clear();
+    DOMINANT_COLOR
+        .lock()
+        .expect("DOMINANT_COLOR poisoned")
+        .cache_clear();
     println!("Configuration reloaded.");
+    Ok(())
 }
 
-pub fn get_filename(
-    tournament_name: &str,
-    round_name: &str,
-    player_1: &str,
-    player_2: &str,
-    extension: &str,
-) -> PathBuf {
-    PathBuf::from(if !round_name.is_empty() {
-        format!("{tournament_name} - {round_name} - {player_1} vs {player_2}.{extension}")
-    } else {
-        format!("{tournament_name} - {player_1} vs {player_2}.{extension}")
-    })
+/// The JSON file `template` was loaded from.
+pub fn template_source(template: &str) -> Result<PathBuf, String> {
+    Ok(get_config(template)?.source)
+}
+
+/// The thumbnail encoding `template` asks for, used as the starting point in the GUI.
+pub fn output_settings(template: &str) -> OutputSettings {
+    get_config(template)
+        .map(|config| config.output)
+        .unwrap_or_default()
+}
+
+/// Video options for `template`, with clip paths relative to the working directory.
+pub fn video_settings(template: &str) -> Result<VideoSettings, String> {
+    let config = get_config(template)?;
+    let base_path = Path::new(&config.base_path);
+    let mut video = config.video;
+    for clip in [&mut video.intro, &mut video.outro].into_iter().flatten() {
+        *clip = base_path.join(&clip);
+    }
+    Ok(video)
 }
 
-#[allow(clippy::too_many_arguments)]
+/// The container metadata for a set, leaving out any that come out empty.
+pub fn video_metadata(template: &str, info: &SetInfo) -> Result<Vec<(String, String)>, String> {
+    get_config(template)?
+        .video
+        .metadata
+        .iter()
+        .map(|(key, value)| {
+            let value = template::render(value, info)?;
+            Ok((key.clone(), value.trim().to_string()))
+        })
+        .filter(|entry| entry.as_ref().map_or(true, |(_, value)| !value.is_empty()))
+        .collect()
+}
+
+/// The output filename pattern for `template`.
+pub fn filename_pattern(template: &str) -> Result<String, String> {
+    Ok(get_config(template)?
+        .filename
+        .unwrap_or_else(|| String::from(DEFAULT_FILENAME)))
+}
+
+/// The endings appended to the output filename for every image `template` produces.
+pub fn thumbnail_suffixes(template: &str, output: &OutputSettings) -> Result<Vec<String>, String> {
+    let extension = output.format.extension();
+    let mut suffixes = vec![format!(".{extension}")];
+    suffixes.extend(
+        get_config(template)?
+            .renditions
+            .iter()
+            .map(|rendition| format!(" - {}.{extension}", sanitize(&rendition.name))),
+    );
+    Ok(suffixes)
+}
+
+/// Writes the thumbnail and every rendition to `output_folder`, named after `stem`.
+///
+/// `frame` is the image used by `vod_frame` layers, if the template has any.
 pub fn write_thumbnail(
-    filename: impl AsRef<Path>,
-    tournament_name: &str,
-    round_name: &str,
-    date: &str,
-    player_1: &str,
-    fighter_1: &str,
-    player_2: &str,
-    fighter_2: &str,
-) {
-    let config = get_config();
+    output_folder: &Path,
+    stem: &str,
+    template: &str,
+    info: &SetInfo,
+    output: &OutputSettings,
+    frame: Option<&RgbaImage>,
+) -> Result<(), String> {
+    let config = get_config(template)?;
+    let suffixes = thumbnail_suffixes(template, output)?;
+    let base_img = render_thumbnail(&config, info, frame)?;
+    for (rendition, suffix) in config.renditions.iter().zip(&suffixes[1..]) {
+        let img = match &rendition.config {
+            Some(rendition_config) => render_thumbnail(rendition_config, info, frame)?,
+            None => fit_image(&base_img, rendition.width, rendition.height, rendition.fit),
+        };
+        save_image(img, &output_folder.join(format!("{stem}{suffix}")), output)?;
+    }
+    save_image(
+        base_img,
+        &output_folder.join(format!("{stem}{}", suffixes[0])),
+        output,
+    )
+}
+
+fn layer_image(
+    config: &Config,
+    layer: &ImageLayer,
+    info: &SetInfo,
+    frame: Option<&RgbaImage>,
+) -> Result<RgbaImage, String> {
+    let img = match &layer.vod_frame {
+        Some(options) => vod_frame_image(config, options, frame)?,
+        None => load_image(&Path::new(&config.base_path).join(&layer.path))?,
+    };
+    apply_effects(img, &layer.effects, info)
+}
+
+fn vod_frame_image(
+    config: &Config,
+    options: &VodFrame,
+    frame: Option<&RgbaImage>,
+) -> Result<RgbaImage, String> {
+    let frame = frame.ok_or("This template needs a frame from the input video")?;
+    let mut img = fit_image(frame, config.width, config.height, options.fit);
+    if options.blur > 0.0 {
+        img = blur(&img, options.blur);
+    }
+    if options.darken > 0.0 {
+        let factor = 1.0 - options.darken.clamp(0.0, 1.0);
+        img.pixels_mut().for_each(|pixel| {
+            for channel in &mut pixel.0[..3] {
+                *channel = (*channel as f32 * factor).round() as u8;
+            }
+        });
+    }
+    Ok(img)
+}
+
+/// Whether any layer of `template` shows a frame from the input video.
+pub fn uses_vod_frame(template: &str) -> bool {
+    get_config(template).is_ok_and(|config| {
+        std::iter::once(&config)
+            .chain(config.renditions.iter().filter_map(|r| r.config.as_deref()))
+            .flat_map(|config| {
+                config
+                    .background_images
+                    .iter()
+                    .chain(&config.foreground_images)
+            })
+            .any(|layer| layer.vod_frame.is_some())
+    })
+}
+
+fn save_image(img: RgbaImage, filename: &Path, output: &OutputSettings) -> Result<(), String> {
+    let bytes = encode(img, output)?;
+    fs::write(filename, bytes).map_err(|e| format!("Could not save the image: {e}"))
+}
+
+fn fit_image(img: &RgbaImage, width: u32, height: u32, fit: Fit) -> RgbaImage {
+    let scale_x = width as f32 / img.width() as f32;
+    let scale_y = height as f32 / img.height() as f32;
+    let scale = match fit {
+        Fit::Stretch => return resize(img, width, height, FilterType::Lanczos3),
+        Fit::Cover => scale_x.max(scale_y),
+        Fit::Contain => scale_x.min(scale_y),
+    };
+    let scaled_width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let scaled_height = ((img.height() as f32 * scale).round() as u32).max(1);
+    let scaled = resize(img, scaled_width, scaled_height, FilterType::Lanczos3);
+    match fit {
+        Fit::Cover => crop_imm(
+            &scaled,
+            scaled_width.saturating_sub(width) / 2,
+            scaled_height.saturating_sub(height) / 2,
+            width.min(scaled_width),
+            height.min(scaled_height),
+        )
+        .to_image(),
+        _ => {
+            let mut canvas = RgbaImage::new(width, height);
+            overlay(
+                &mut canvas,
+                &scaled,
+                (width as i64 - scaled_width as i64) / 2,
+                (height as i64 - scaled_height as i64) / 2,
+            );
+            canvas
+        }
+    }
+}
+
+fn render_thumbnail(
+    config: &Config,
+    info: &SetInfo,
+    frame: Option<&RgbaImage>,
+) -> Result<RgbaImage, String> {
+    let base_path = Path::new(&config.base_path);
+    let info = &with_accents(config, info)?;
     let mut base_img = RgbaImage::new(config.width, config.height);
     let mut layers = Vec::new();
-    config
-        .background_images
-        .iter()
-        .for_each(|filename| layers.push(load_image(filename)));
-    layers.push(load_image(
-        Path::new(&config.char_img_path)
-            .join(fighter_1)
-            .to_str()
-            .expect("Could not build fighter_1 filepath"),
-    ));
-    layers.push(load_image(
-        Path::new(&config.char_img_path)
-            .join(fighter_2)
-            .to_str()
-            .expect("Could not build fighter_1 filepath"),
-    ));
-    config
-        .foreground_images
-        .iter()
-        .for_each(|filename| layers.push(load_image(filename)));
-    config.positioned_texts.iter().for_each(|positioned_text| {
-        let to_draw = match positioned_text.text.as_ref() {
-            "TOURNAMENT_NAME" => PositionedText {
-                text: tournament_name.to_string(),
-                ..*positioned_text
-            },
-            "PLAYER_1" => PositionedText {
-                text: player_1.to_string(),
-                ..*positioned_text
-            },
-            "PLAYER_2" => PositionedText {
-                text: player_2.to_string(),
-                ..*positioned_text
-            },
-            "ROUND_NAME" => PositionedText {
-                text: round_name.to_string(),
-                ..*positioned_text
+    for layer in &config.background_images {
+        if layer.conditions.matches(info)? {
+            layers.push((
+                layer_image(config, layer, info, frame)?,
+                (layer.x as i64, layer.y as i64),
+                layer.blend,
+            ));
+        }
+    }
+    for (side, fighter) in [(1, &info.fighter_1), (2, &info.fighter_2)] {
+        let img = load_image(&base_path.join(&config.char_img_path).join(fighter))?;
+        if config.grey_out_loser && info.loser() == Some(side) {
+            layers.push((
+                DynamicImage::from(grayscale_alpha(&img)).to_rgba8(),
+                (0, 0),
+                BlendMode::Normal,
+            ));
+        } else {
+            layers.push((img, (0, 0), BlendMode::Normal));
+        }
+    }
+    for layer in &config.foreground_images {
+        if layer.conditions.matches(info)? {
+            layers.push((
+                layer_image(config, layer, info, frame)?,
+                (layer.x as i64, layer.y as i64),
+                layer.blend,
+            ));
+        }
+    }
+    for positioned_text in &config.positioned_texts {
+        if !positioned_text.conditions.matches(info)? {
+            continue;
+        }
+        let text = text_content(positioned_text, info)?;
+        let fill = TextFill {
+            color: match &positioned_text.color {
+                Some(color) => resolve_color(color, info)?,
+                None => DEFAULT_TEXT_COLOR,
             },
-            "DATE" => PositionedText {
-                text: date.to_string(),
-                ..*positioned_text
-            },
-            _ => positioned_text.clone(),
+            gradient: positioned_text
+                .gradient
+                .as_ref()
+                .map(|gradient| gradient.resolved(info))
+                .transpose()?,
+            char_colors: positioned_text
+                .char_colors
+                .iter()
+                .map(|color| resolve_color(color, info))
+                .collect::<Result<_, _>>()?,
         };
-        layers.push(draw_centered_text(
-            config.width,
-            config.height,
-            &to_draw.text,
-            to_draw.x,
-            to_draw.y,
-            to_draw.scale,
-            to_draw.theta,
+        layers.push((
+            draw_centered_text(config, &text, positioned_text, &fill)?,
+            (0, 0),
+            BlendMode::Normal,
         ))
-    });
+    }
     layers
         .iter()
-        .for_each(|layer| overlay(&mut base_img, layer, 0, 0));
-    let base_img = rgba8_to_rgb8(base_img);
-    base_img.save(filename).expect("Could not save the image");
+        .for_each(|(layer, (x, y), blend)| composite(&mut base_img, layer, *x, *y, *blend));
+    Ok(base_img)
+}
+
+/// The text to draw for `positioned_text`, with tokens and `transform` applied.
+fn text_content(positioned_text: &PositionedText, info: &SetInfo) -> Result<String, String> {
+    let text = template::render(&positioned_text.text, info)?;
+    Ok(match positioned_text.transform {
+        Some(transform) => transform.apply(&text),
+        None => text,
+    })
+}
+
+/// Copies `info` with the accent colors of both characters filled in.
+fn with_accents(config: &Config, info: &SetInfo) -> Result<SetInfo, String> {
+    let mut info = info.clone();
+    info.accent_1 = accent_color(config, &info.fighter_1)?;
+    info.accent_2 = accent_color(config, &info.fighter_2)?;
+    Ok(info)
+}
+
+/// The accent declared in the character metadata, or else the dominant color of the art.
+fn accent_color(config: &Config, fighter: &str) -> Result<String, String> {
+    if let Some(accent) = config
+        .assets
+        .character_metadata
+        .get(fighter)
+        .and_then(|character| character.accent.clone())
+    {
+        return Ok(accent);
+    }
+    let path = Path::new(&config.base_path)
+        .join(&config.char_img_path)
+        .join(fighter);
+    Ok(format_color(dominant_color(&path)?))
+}
+
+/// Something in a template that the layout editor can select and move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutItem {
+    Background(usize),
+    Foreground(usize),
+    Text(usize),
+}
+
+/// Where each item of a preview was drawn, bottom to top.
+pub type Placements = Vec<(LayoutItem, Bounds)>;
+
+/// A rectangle on the thumbnail canvas, in pixels, possibly slanted and turned like a
+/// positioned text.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Slant in degrees, applied before `theta`.
+    pub skew_x: f32,
+    /// Clockwise rotation in radians.
+    pub theta: f32,
+    /// The point the rectangle is slanted and turned around.
+    pub pivot: (f32, f32),
+}
+
+impl Bounds {
+    /// An upright rectangle.
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Bounds {
+        Bounds {
+            x,
+            y,
+            width,
+            height,
+            skew_x: 0.0,
+            theta: 0.0,
+            pivot: (x as f32, y as f32),
+        }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let (x, y) = self.unturn(x, y);
+        x >= self.x as f32
+            && y >= self.y as f32
+            && x < (self.x + self.width as i32) as f32
+            && y < (self.y + self.height as i32) as f32
+    }
+
+    /// The corners as drawn on the canvas, clockwise from the top left.
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        let (left, top) = (self.x as f32, self.y as f32);
+        let (right, bottom) = (left + self.width as f32, top + self.height as f32);
+        [(left, top), (right, top), (right, bottom), (left, bottom)].map(|(x, y)| self.turn(x, y))
+    }
+
+    /// Undoes the rotation and then the slant, the reverse of how the text was drawn.
+    fn unturn(&self, x: f32, y: f32) -> (f32, f32) {
+        let (px, py) = self.pivot;
+        let (sin, cos) = self.theta.sin_cos();
+        let (dx, dy) = (x - px, y - py);
+        let (dx, dy) = (dx * cos + dy * sin, dy * cos - dx * sin);
+        (px + dx + self.skew_x.to_radians().tan() * dy, py + dy)
+    }
+
+    /// Slants and then turns a point, the way the text was drawn.
+    fn turn(&self, x: f32, y: f32) -> (f32, f32) {
+        let (px, py) = self.pivot;
+        let (sin, cos) = self.theta.sin_cos();
+        let (dx, dy) = (x - px - self.skew_x.to_radians().tan() * (y - py), y - py);
+        (px + dx * cos - dy * sin, py + dx * sin + dy * cos)
+    }
+}
+
+/// Renders a template that hasn't been saved, along with where each drawn item ended up,
+/// bottom to top. `vod_frame` layers show a plain grey frame.
+///
+/// `assets` from an earlier preview are reused unless the template now points at other ones,
+/// so an edit only re-reads the layout. The assets used are returned for the next preview.
+pub fn preview(
+    document: &Value,
+    info: &SetInfo,
+    assets: Option<Arc<Assets>>,
+) -> Result<(RgbaImage, Placements, Arc<Assets>), String> {
+    let mut config = layout_from_value(document.clone(), "the template")?;
+    config.assets = match assets {
+        Some(assets) if assets.source == assets_source(&config) => assets,
+        _ => Arc::new(load_assets(&config, "the template")?),
+    };
+    let frame = RgbaImage::from_pixel(config.width, config.height, Rgba([64, 64, 64, 255]));
+    let img = render_thumbnail(&config, info, Some(&frame))?;
+    let info = &with_accents(&config, info)?;
+    let canvas = Bounds::new(0, 0, config.width, config.height);
+    let mut items = Vec::new();
+    let layers = [
+        (
+            &config.background_images,
+            LayoutItem::Background as fn(usize) -> LayoutItem,
+        ),
+        (&config.foreground_images, LayoutItem::Foreground),
+    ];
+    for (layers, item) in layers {
+        for (i, layer) in layers.iter().enumerate() {
+            if !layer.conditions.matches(info)? {
+                continue;
+            }
+            let bounds = match &layer.vod_frame {
+                Some(_) => canvas,
+                None => {
+                    let img = load_image(&Path::new(&config.base_path).join(&layer.path))?;
+                    // Banners are often canvas-sized with lots of transparency,
+                    // so only the visible part counts when clicking.
+                    let Some(visible) = visible_bounds(&img) else {
+                        continue;
+                    };
+                    Bounds::new(
+                        visible.x + layer.x,
+                        visible.y + layer.y,
+                        visible.width,
+                        visible.height,
+                    )
+                }
+            };
+            items.push((item(i), bounds));
+        }
+    }
+    for (i, positioned_text) in config.positioned_texts.iter().enumerate() {
+        if !positioned_text.conditions.matches(info)? {
+            continue;
+        }
+        let block =
+            text_style(&config, positioned_text).layout(&text_content(positioned_text, info)?);
+        items.push((
+            LayoutItem::Text(i),
+            Bounds {
+                skew_x: positioned_text.skew_x,
+                theta: positioned_text.theta,
+                pivot: (positioned_text.x as f32, positioned_text.y as f32),
+                ..Bounds::new(
+                    positioned_text.x - block.width as i32 / 2,
+                    positioned_text.y - block.height as i32 / 2,
+                    block.width,
+                    block.height,
+                )
+            },
+        ));
+    }
+    Ok((img, items, config.assets))
+}
+
+/// The smallest rectangle containing every non-transparent pixel of `img`.
+fn visible_bounds(img: &RgbaImage) -> Option<Bounds> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if pixel.0[3] > 0 {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    (min_x <= max_x).then(|| {
+        Bounds::new(
+            min_x as i32,
+            min_y as i32,
+            max_x - min_x + 1,
+            max_y - min_y + 1,
+        )
+    })
+}
+
+/// Encodes the thumbnail, stepping down JPEG quality to meet `max_file_size`.
+fn encode(img: RgbaImage, output: &OutputSettings) -> Result<Vec<u8>, String> {
+    let fits = |bytes: &Vec<u8>| {
+        output
+            .max_file_size
+            .is_none_or(|max| bytes.len() as u64 <= max)
+    };
+    let mut bytes = Vec::new();
+    match output.format {
+        OutputFormat::Jpeg => {
+            let img = rgba8_to_rgb8(img);
+            let mut quality = output.quality.clamp(1, 100);
+            loop {
+                bytes.clear();
+                JpegEncoder::new_with_quality(&mut bytes, quality)
+                    .encode_image(&img)
+                    .map_err(|e| format!("Could not encode the image: {e}"))?;
+                if fits(&bytes) || quality <= MIN_JPEG_QUALITY {
+                    break;
+                }
+                quality = quality
+                    .saturating_sub(JPEG_QUALITY_STEP)
+                    .max(MIN_JPEG_QUALITY);
+            }
+        }
+        OutputFormat::Png => {
+            img.write_with_encoder(PngEncoder::new_with_quality(
+                &mut bytes,
+                CompressionType::Best,
+                PngFilterType::Adaptive,
+            ))
+            .map_err(|e| format!("Could not encode the image: {e}"))?;
+        }
+        OutputFormat::Webp => {
+            img.write_with_encoder(WebPEncoder::new_lossless(&mut bytes))
+                .map_err(|e| format!("Could not encode the image: {e}"))?;
+        }
+    }
+    if !fits(&bytes) {
+        return Err(format!(
+            "Thumbnail is {} KB, over the {} KB limit",
+            bytes.len() / 1000,
+            output.max_file_size.unwrap_or_default() / 1000
+        ));
+    }
+    Ok(bytes)
+}
+
+fn text_style<'a>(config: &'a Config, style: &PositionedText) -> TextStyle<'a> {
+    TextStyle {
+        font: FontRef::try_from_slice(&config.assets.font_data).expect("Font was checked on load"),
+        scale: PxScale::from(style.scale),
+        letter_spacing: style.letter_spacing,
+        line_height: style.line_height,
+        max_width: style.max_width,
+        align: style.align,
+    }
+}
+
+/// Everything that affects the output of `draw_centered_text`.
+fn text_cache_key(config: &Config, text: &str, style: &PositionedText, fill: &TextFill) -> String {
+    format!(
+        "{}|{}x{}|{text}|{style:?}|{fill:?}",
+        Path::new(&config.base_path).join(&config.font).display(),
+        config.width,
+        config.height,
+    )
 }
 
+// Each entry is a full canvas and every edit in the layout editor makes a new one, so only
+// the most recent are kept.
 #[cached(
-    key = "(String, i32, i32, i32, i32)",
-    convert = r#"{ (String::from(text), x_px, y_px, (scale * 1000.0) as i32, (rotation * 1000.0) as i32) }"#
+    size = 32,
+    key = "String",
+    convert = r#"{ text_cache_key(config, text, style, fill) }"#,
+    result = true
 )]
 fn draw_centered_text(
-    width: u32,
-    height: u32,
+    config: &Config,
     text: &str,
-    x_px: i32,
-    y_px: i32,
-    scale: f32,
-    rotation: f32,
-) -> ImageBuffer<Rgba<u8>, Vec<<Rgba<u8> as image::Pixel>::Subpixel>> {
-    let mut img = RgbaImage::new(width, height);
-    let binding = FONT_BYTES.read().expect("FONT_BYTES poisoned");
-    let font = FontRef::try_from_slice(&binding).expect("Could not load the font!");
-    let pxscale = PxScale::from(scale);
-    let size = text_size(pxscale, &font, text);
-    let color = Rgba([227, 228, 229, 255]);
-    draw_text_mut(
+    style: &PositionedText,
+    fill: &TextFill,
+) -> Result<RgbaImage, String> {
+    let (x_px, y_px) = (style.x, style.y);
+    let mut img = RgbaImage::new(config.width, config.height);
+    let text_style = text_style(config, style);
+    let block = text_style.layout(text);
+    text_style.draw(
         &mut img,
-        color,
-        x_px - (size.0 as i32 / 2),
-        y_px - (size.1 as i32 / 2),
-        pxscale,
-        &font,
-        text,
-    );
-    rotate(
+        fill,
+        x_px - (block.width as i32 / 2),
+        y_px - (block.height as i32 / 2),
+        &block,
+    )?;
+    // Skew then rotate around the anchor point, so the text stays centered on it.
+    let (cx, cy) = (x_px as f32, y_px as f32);
+    let skew = style.skew_x.to_radians().tan();
+    let shear = Projection::from_matrix([1.0, -skew, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0])
+        .expect("Shears are invertible");
+    let projection = Projection::translate(cx, cy)
+        * Projection::rotate(style.theta)
+        * shear
+        * Projection::translate(-cx, -cy);
+    Ok(warp(
         &img,
-        (x_px as f32, y_px as f32),
-        rotation,
+        &projection,
         Interpolation::Bicubic,
         Rgba([0, 0, 0, 0]),
-    )
+    ))
 }
 
 fn rgba8_to_rgb8(input: ImageBuffer<Rgba<u8>, Vec<u8>>) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
     input.convert()
 }
 
-#[cached(key = "String", convert = r#"{ String::from(filename) }"#)]
-fn load_image(filename: &str) -> RgbaImage {
-    open(Path::new(&get_config().base_path).join(filename))
-        .unwrap_or_else(|_| panic!("Couldn't open {filename}"))
-        .to_rgba8()
+#[cached(key = "PathBuf", convert = r#"{ path.to_path_buf() }"#, result = true)]
+fn load_image(path: &Path) -> Result<RgbaImage, String> {
+    Ok(open(path)
+        .map_err(|e| format!("Couldn't open {}: {e}", path.display()))?
+        .to_rgba8())
+}
+
+#[cached(key = "PathBuf", convert = r#"{ path.to_path_buf() }"#, result = true)]
+fn dominant_color(path: &Path) -> Result<Rgba<u8>, String> {
+    Ok(characters::dominant_color(&load_image(path)?).unwrap_or(DEFAULT_TEXT_COLOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn upright_bounds_contain_their_inside() {
+        let bounds = Bounds::new(10, 20, 100, 10);
+        assert!(bounds.contains(10.0, 20.0));
+        assert!(bounds.contains(109.0, 29.0));
+        assert!(!bounds.contains(110.0, 25.0));
+        assert!(!bounds.contains(50.0, 19.0));
+    }
+
+    #[test]
+    fn turned_bounds_are_hit_where_they_are_drawn() {
+        // A wide, short box turned a quarter clockwise around its center becomes tall and thin.
+        let bounds = Bounds {
+            theta: FRAC_PI_2,
+            pivot: (60.0, 25.0),
+            ..Bounds::new(10, 20, 100, 10)
+        };
+        assert!(bounds.contains(60.0, 70.0));
+        assert!(bounds.contains(60.0, -20.0));
+        assert!(!bounds.contains(100.0, 25.0));
+    }
+
+    #[test]
+    fn slanted_bounds_lean_right() {
+        let bounds = Bounds {
+            skew_x: 45.0,
+            pivot: (50.0, 50.0),
+            ..Bounds::new(40, 0, 20, 100)
+        };
+        // The top leans right and the bottom left, by as much as each is from the pivot.
+        assert!(bounds.contains(95.0, 5.0));
+        assert!(!bounds.contains(45.0, 5.0));
+        assert!(bounds.contains(5.0, 95.0));
+        assert!(bounds.contains(50.0, 50.0));
+    }
+
+    #[test]
+    fn corners_follow_the_turn() {
+        let bounds = Bounds {
+            theta: FRAC_PI_2,
+            pivot: (0.0, 0.0),
+            ..Bounds::new(0, 0, 10, 2)
+        };
+        let [top_left, top_right, ..] = bounds.corners();
+        assert!(top_left.0.abs() < 1e-4 && top_left.1.abs() < 1e-4);
+        assert!(top_right.0.abs() < 1e-4 && (top_right.1 - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn size_limits_are_only_for_jpeg() {
+        let limited = |format| OutputSettings {
+            format,
+            max_file_size: Some(2_000_000),
+            ..OutputSettings::default()
+        };
+        assert!(limited(OutputFormat::Jpeg).validate().is_ok());
+        assert!(limited(OutputFormat::Png).validate().is_err());
+        assert!(limited(OutputFormat::Webp).validate().is_err());
+        let unlimited = OutputSettings {
+            format: OutputFormat::Png,
+            ..OutputSettings::default()
+        };
+        assert!(unlimited.validate().is_ok());
+    }
 }
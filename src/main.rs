@@ -1,18 +1,45 @@
 #![windows_subsystem = "windows"]
 use iced::{
     self,
-    widget::{self, pick_list, Button, Checkbox, Column, Row, Text, TextInput},
+    widget::{
+        self, image::Handle, pick_list, scrollable, Button, Checkbox, Column, Row, Text, TextInput,
+    },
     window::icon,
     window::Settings,
     Element, Task,
 };
+mod audio;
+mod batch;
+mod blend;
+mod characters;
+mod condition;
+mod detect;
+mod editor;
+mod effects;
+mod filename;
+mod filmstrip;
 mod img;
-use img::{get_filename, write_thumbnail};
+use audio::{AudioMessage, TrackRow};
+use batch::{BatchMessage, Segment};
+use editor::{Editor, EditorMessage};
+use filename::get_filename;
+use filmstrip::Filmstrip;
+use img::{write_thumbnail, OutputFormat, OutputSettings};
+use scrubber::{decode_frame, Scrubber};
+mod scrubber;
+mod settings;
+mod template;
+mod text;
+mod timeline;
 mod video;
-use img::CHAR_IMGS;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use template::SetInfo;
+use timeline::Timeline;
 use tokio::task;
-use video::trim_video;
+use video::{
+    add_bumpers, attach_cover, format_timestamp, normalize_loudness, parse_timestamp,
+    write_metadata, TrimOptions, FFMPEG_PATH,
+};
 
 fn main() -> iced::Result {
     let ico = icon::from_file_data(include_bytes!("icon.ico"), None).expect("Couldn't load icon");
@@ -27,10 +54,17 @@ fn main() -> iced::Result {
 #[derive(Clone, Debug)]
 enum Message {
     End,
-    InputFile(String),
+    InputFile(usize, String),
     BrowseFile,
+    AddInputFile,
+    RemoveInputFile(usize),
     OutputFolder(String),
     BrowserFolder,
+    Template(String),
+    SetDefaultTemplate,
+    OutputFormat(OutputFormat),
+    Quality(String),
+    MaxFileSize(String),
     TournamentName(String),
     RoundName(String),
     Date(String),
@@ -38,19 +72,50 @@ enum Message {
     Fighter1(String),
     Player2(String),
     Fighter2(String),
+    Score1(String),
+    Score2(String),
     StartTime(String),
     EndTime(String),
+    FrameTime(String),
     UpdateMsg(String),
     GenerateThumbnail(bool),
     GenerateVideo(bool),
+    TeamMode(bool),
     Submit,
     ReloadConfig,
+    OpenEditor,
+    Editor(EditorMessage),
+    SaveTemplate,
+    CloseEditor,
+    LoadPreview,
+    PreviewLoaded(Result<Scrubber, String>),
+    SeekPreview(f64),
+    DecodePreview,
+    StepPreview(f64),
+    PreviewFrame(f64, Result<Handle, String>),
+    SetStartHere,
+    SetEndHere,
+    FilmstripInterval(String),
+    BuildFilmstrip,
+    FilmstripBuilt(Vec<String>, Result<Filmstrip, String>),
+    JumpTo(f64),
+    AddToBatch,
+    TrimBatch,
+    Batch(BatchMessage),
+    Audio(AudioMessage),
+    DetectSets,
+    SetsDetected(Result<Vec<(f64, f64)>, String>),
 }
 
 #[derive(Clone)]
 struct App {
-    input_file: String,
+    /// Played back to back as one continuous video.
+    input_files: Vec<String>,
     output_folder: String,
+    template: String,
+    output_format: OutputFormat,
+    quality: String,
+    max_file_size: String,
     tournament_name: String,
     round_name: String,
     date: String,
@@ -58,52 +123,456 @@ struct App {
     fighter_1: String,
     player_2: String,
     fighter_2: String,
+    score_1: String,
+    score_2: String,
     start_time: String,
     end_time: String,
+    frame_time: String,
     message: String,
     generate_thumbnail: bool,
     generate_video: bool,
+    team_mode: bool,
+    /// The layout editor, shown instead of the main form while open.
+    editor: Option<Editor>,
+    /// Preview of the input video, once loaded.
+    scrubber: Option<Scrubber>,
+    /// Seconds between filmstrip tiles.
+    filmstrip_interval: String,
+    filmstrip: Option<Filmstrip>,
+    /// Sets to cut out of the input in one pass.
+    batch: Vec<Segment>,
+    /// What to do with each of the input's audio tracks, once a preview is loaded.
+    audio_tracks: Vec<TrackRow>,
 }
 
 impl Default for App {
     fn default() -> Self {
-        App {
-            input_file: String::new(),
+        let template = img::default_template();
+        let fighter = img::characters(&template)
+            .first()
+            .cloned()
+            .unwrap_or_default();
+        let mut app = App {
+            input_files: vec![String::new()],
             output_folder: String::new(),
+            template,
+            output_format: OutputFormat::default(),
+            quality: String::new(),
+            max_file_size: String::new(),
             tournament_name: String::new(),
             round_name: String::new(),
             date: String::new(),
             player_1: String::new(),
-            fighter_1: CHAR_IMGS.read().expect("Poisoned CHAR_IMGS")[0].clone(),
+            fighter_1: fighter.clone(),
             player_2: String::new(),
-            fighter_2: CHAR_IMGS.read().expect("Poisoned CHAR_IMGS")[0].clone(),
+            fighter_2: fighter,
+            score_1: String::new(),
+            score_2: String::new(),
             start_time: String::from("00:00:00"),
             end_time: String::from("00:00:00"),
-            message: String::new(),
+            frame_time: String::new(),
+            message: img::skipped_templates().join("\n"),
             generate_thumbnail: true,
             generate_video: true,
-        }
+            team_mode: false,
+            editor: None,
+            scrubber: None,
+            filmstrip_interval: String::from("30"),
+            filmstrip: None,
+            batch: Vec::new(),
+            audio_tracks: Vec::new(),
+        };
+        app.reset_output_settings();
+        app
     }
 }
 
 impl App {
+    fn set_info(&self) -> SetInfo {
+        SetInfo {
+            tournament_name: self.tournament_name.clone(),
+            round_name: self.round_name.clone(),
+            date: self.date.clone(),
+            player_1: self.player_1.clone(),
+            fighter_1: self.fighter_1.clone(),
+            player_2: self.player_2.clone(),
+            fighter_2: self.fighter_2.clone(),
+            score_1: self.score_1.clone(),
+            score_2: self.score_2.clone(),
+            team_mode: self.team_mode,
+            ..SetInfo::default()
+        }
+    }
+
+    fn reset_output_settings(&mut self) {
+        let output = img::output_settings(&self.template);
+        self.output_format = output.format;
+        self.quality = output.quality.to_string();
+        self.max_file_size = output
+            .max_file_size
+            .map(|bytes| (bytes / 1000).to_string())
+            .unwrap_or_default();
+    }
+
+    fn output_settings(&self) -> Result<OutputSettings, String> {
+        // Only JPEG has a quality setting, so don't hold up other formats over the field.
+        let quality = if self.output_format == OutputFormat::Jpeg {
+            self.quality
+                .trim()
+                .parse()
+                .ok()
+                .filter(|quality| (1..=100).contains(quality))
+                .ok_or("JPEG quality must be a number from 1 to 100")?
+        } else {
+            img::output_settings(&self.template).quality
+        };
+        let max_file_size = match self.max_file_size.trim() {
+            "" => None,
+            kb => Some(
+                kb.parse::<u64>()
+                    .map_err(|_| "Max size must be a number of KB")?
+                    .checked_mul(1000)
+                    .ok_or("Max size is too large")?,
+            ),
+        };
+        let output = OutputSettings {
+            format: self.output_format,
+            quality,
+            max_file_size,
+        };
+        output.validate()?;
+        Ok(output)
+    }
+
+    /// Fighters are per-template, so fall back to the first one if the selection is gone.
+    fn reset_missing_fighters(&mut self) {
+        let characters = img::characters(&self.template);
+        for fighter in [&mut self.fighter_1, &mut self.fighter_2] {
+            if !characters.contains(fighter) {
+                *fighter = characters.first().cloned().unwrap_or_default();
+            }
+        }
+    }
+
+    /// Grabs the frame for `vod_frame` layers, defaulting to the middle of the set.
+    fn thumbnail_frame(&self) -> Result<image::RgbaImage, String> {
+        let time = if self.frame_time.trim().is_empty() {
+            let (start, end) = self.trim_times()?;
+            (start + end) / 2.0
+        } else {
+            parse_timestamp(&self.frame_time).ok_or("Invalid thumbnail frame time")?
+        };
+        Timeline::probe(&self.input_files)?.frame(time)
+    }
+
+    fn trim_times(&self) -> Result<(f64, f64), String> {
+        let start = parse_timestamp(&self.start_time).ok_or("Invalid starting time")?;
+        let end = parse_timestamp(&self.end_time).ok_or("Invalid ending time")?;
+        Ok((start, end))
+    }
+
+    fn reload_config(&mut self) -> Result<(), String> {
+        img::reload_config()?;
+        if !img::template_names().contains(&self.template) {
+            self.template = img::default_template();
+        }
+        self.reset_missing_fighters();
+        Ok(())
+    }
+
+    /// Decodes the frame under the preview's playhead in the background.
+    fn decode_preview(&self) -> Task<Message> {
+        let Some(scrubber) = &self.scrubber else {
+            return Task::none();
+        };
+        let (timeline, position) = (scrubber.timeline.clone(), scrubber.position);
+        Task::future(async move {
+            let frame = task::spawn_blocking(move || decode_frame(&timeline, position))
+                .await
+                .unwrap();
+            Message::PreviewFrame(position, frame)
+        })
+    }
+
+    fn run_job(&self) -> Result<String, String> {
+        let info = self.set_info();
+        let output = self.output_settings()?;
+        let output_folder = PathBuf::from(&self.output_folder);
+        let mut suffixes = Vec::new();
+        if self.generate_thumbnail {
+            suffixes.extend(img::thumbnail_suffixes(&self.template, &output)?);
+        }
+        if self.generate_video {
+            suffixes.push(String::from(".mp4"));
+        }
+        let stem = get_filename(
+            &output_folder,
+            &img::filename_pattern(&self.template)?,
+            &info,
+            &suffixes,
+        )?;
+
+        let mut msg = String::from("Finished");
+        let mut notes = Vec::new();
+        if self.generate_thumbnail {
+            let frame = if img::uses_vod_frame(&self.template) {
+                Some(self.thumbnail_frame()?)
+            } else {
+                None
+            };
+            write_thumbnail(
+                &output_folder,
+                &stem,
+                &self.template,
+                &info,
+                &output,
+                frame.as_ref(),
+            )?;
+            msg.push_str(" generating thumbnail");
+            if self.generate_video {
+                msg.push_str(" and");
+            }
+        }
+
+        if self.generate_video {
+            let filename_mp4 = output_folder.join(format!("{stem}.mp4"));
+            let (start, end) = self.trim_times()?;
+            let options = TrimOptions {
+                metadata: img::video_metadata(&self.template, &info)?,
+                audio: audio::tracks(&self.audio_tracks)?,
+            };
+            Timeline::probe(&self.input_files)?.trim(&filename_mp4, start, end, &options)?;
+            notes = self.finish_video(&filename_mp4, &self.template, &options.metadata)?;
+            if self.generate_thumbnail {
+                let thumbnail = output_folder.join(format!("{stem}.{}", output.format.extension()));
+                attach_cover(FFMPEG_PATH, &filename_mp4, &thumbnail)?;
+            }
+            msg.push_str(" generating video");
+        }
+        msg.push('!');
+        for note in notes {
+            msg.push(' ');
+            msg.push_str(&note);
+        }
+        Ok(msg)
+    }
+
+    /// A new batch row for `start` to `end`, with the form's template and set details.
+    fn batch_segment(&self, start: String, end: String) -> Result<Segment, String> {
+        let info = self.set_info();
+        Ok(Segment {
+            start,
+            end,
+            name: self.batch_name(None, &self.template, &info)?,
+            template: self.template.clone(),
+            info,
+            expanded: false,
+        })
+    }
+
+    /// A new batch row for a detected set. Only the details shared by the whole VOD come from
+    /// the form; the row opens its details so the rest can be filled in.
+    fn detected_segment(&self, start: f64, end: f64) -> Result<Segment, String> {
+        let info = SetInfo {
+            tournament_name: self.tournament_name.clone(),
+            date: self.date.clone(),
+            fighter_1: self.fighter_1.clone(),
+            fighter_2: self.fighter_2.clone(),
+            team_mode: self.team_mode,
+            ..SetInfo::default()
+        };
+        let mut segment = Segment {
+            start: format_timestamp(start),
+            end: format_timestamp(end),
+            name: String::new(),
+            template: self.template.clone(),
+            info,
+            expanded: true,
+        };
+        segment.name = self.batch_name(None, &segment.template, &segment.info)?;
+        Ok(segment)
+    }
+
+    /// A filename for batch row `row` (or a new row) from `template`'s filename pattern.
+    fn batch_name(
+        &self,
+        row: Option<usize>,
+        template: &str,
+        info: &SetInfo,
+    ) -> Result<String, String> {
+        let name = get_filename(
+            Path::new(&self.output_folder),
+            &img::filename_pattern(template)?,
+            info,
+            &[String::from(".mp4")],
+        )?;
+        Ok(batch::unique_name(&self.batch, row, name))
+    }
+
+    /// The filename batch row `index` gets from its own template and details.
+    fn batch_row_name(&self, index: usize) -> Result<String, String> {
+        let segment = &self.batch[index];
+        self.batch_name(Some(index), &segment.template, &segment.info)
+    }
+
+    /// Image settings for a batch row: the form's for its own template, otherwise the
+    /// row template's defaults.
+    fn batch_output_settings(&self, template: &str) -> Result<OutputSettings, String> {
+        if template == self.template {
+            self.output_settings()
+        } else {
+            Ok(img::output_settings(template))
+        }
+    }
+
+    /// Cuts every set in the batch out of the input in a single pass, then finishes each one
+    /// with its own template and details.
+    fn run_batch(&self) -> Result<String, String> {
+        let output_folder = Path::new(&self.output_folder);
+        let ranges = batch::ranges(&self.batch, output_folder)?;
+        let mut thumbnails = Vec::new();
+        if self.generate_thumbnail {
+            for (segment, (_, _, video)) in self.batch.iter().zip(&ranges) {
+                let output = self.batch_output_settings(&segment.template)?;
+                let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+                for suffix in img::thumbnail_suffixes(&segment.template, &output)? {
+                    let path = output_folder.join(format!("{stem}{suffix}"));
+                    if path.exists() {
+                        return Err(format!("{} already exists", path.display()));
+                    }
+                }
+                thumbnails.push(output);
+            }
+        }
+        let options = TrimOptions {
+            metadata: Vec::new(),
+            audio: audio::tracks(&self.audio_tracks)?,
+        };
+        let timeline = Timeline::probe(&self.input_files)?;
+        timeline.trim_all(&ranges, &options)?;
+        let mut notes = Vec::new();
+        for (index, (segment, (start, end, video))) in self.batch.iter().zip(&ranges).enumerate() {
+            // A single pass can't give each set its own details, so they're written afterwards.
+            let metadata = img::video_metadata(&segment.template, &segment.info)?;
+            write_metadata(FFMPEG_PATH, video, &metadata)?;
+            for note in self.finish_video(video, &segment.template, &metadata)? {
+                // Every set comes from the same VOD, so bumper warnings repeat.
+                if !notes.contains(&note) {
+                    notes.push(note);
+                }
+            }
+            if let Some(output) = thumbnails.get(index) {
+                let frame = if img::uses_vod_frame(&segment.template) {
+                    Some(timeline.frame((start + end) / 2.0)?)
+                } else {
+                    None
+                };
+                let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+                write_thumbnail(
+                    output_folder,
+                    &stem,
+                    &segment.template,
+                    &segment.info,
+                    output,
+                    frame.as_ref(),
+                )?;
+                let thumbnail = output_folder.join(format!("{stem}.{}", output.format.extension()));
+                attach_cover(FFMPEG_PATH, video, &thumbnail)?;
+            }
+        }
+        let mut msg = format!("Finished trimming {} sets", ranges.len());
+        if self.generate_thumbnail {
+            msg.push_str(" and generating their thumbnails");
+        }
+        msg.push('!');
+        for note in notes {
+            msg.push(' ');
+            msg.push_str(&note);
+        }
+        Ok(msg)
+    }
+
+    /// Normalizes a trimmed set's loudness and joins `template`'s intro and outro clips onto
+    /// it, returning anything worth reporting.
+    fn finish_video(
+        &self,
+        video: &Path,
+        template: &str,
+        metadata: &[(String, String)],
+    ) -> Result<Vec<String>, String> {
+        let settings = img::video_settings(template)?;
+        let mut notes = Vec::new();
+        if let Some(loudness) = &settings.loudness {
+            let measurements = normalize_loudness(FFMPEG_PATH, video, loudness)?;
+            let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+            for (index, measured) in measurements.iter().enumerate() {
+                let track = if measurements.len() > 1 {
+                    format!(" track {}", index + 1)
+                } else {
+                    String::new()
+                };
+                notes.push(format!(
+                    "{stem}{track} measured {:.1} LUFS, {:.1} dBTP peak, {:.1} LU range.",
+                    measured.integrated, measured.true_peak, measured.range
+                ));
+            }
+        }
+        notes.extend(add_bumpers(
+            FFMPEG_PATH,
+            video,
+            settings.intro.as_deref(),
+            settings.outro.as_deref(),
+            metadata,
+        )?);
+        Ok(notes)
+    }
+
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::End => return Task::none(),
-            Message::InputFile(message) => {
-                self.input_file = message;
+            Message::InputFile(index, message) => {
+                self.input_files[index] = message;
+                self.scrubber = None;
+                self.filmstrip = None;
+                self.audio_tracks.clear();
             }
             Message::BrowseFile => {
+                let path = std::env::current_dir().unwrap();
+                if let Some(mut res) = rfd::FileDialog::new()
+                    .set_directory(&path)
+                    .add_filter(".mp4", &["mp4"])
+                    .pick_files()
+                {
+                    // Recordings are usually named by when they started.
+                    res.sort();
+                    self.input_files = res
+                        .iter()
+                        .map(|path| path.to_str().unwrap().to_string())
+                        .collect();
+                    self.filmstrip = None;
+                    self.audio_tracks.clear();
+                    return Task::done(Message::LoadPreview);
+                }
+            }
+            Message::AddInputFile => {
                 let path = std::env::current_dir().unwrap();
                 if let Some(res) = rfd::FileDialog::new()
                     .set_directory(&path)
                     .add_filter(".mp4", &["mp4"])
                     .pick_file()
                 {
-                    let path = res.to_str().unwrap();
-                    self.input_file = path.to_string();
+                    self.input_files.push(res.to_str().unwrap().to_string());
+                    self.filmstrip = None;
+                    self.audio_tracks.clear();
+                    return Task::done(Message::LoadPreview);
                 }
             }
+            Message::RemoveInputFile(index) => {
+                self.input_files.remove(index);
+                self.scrubber = None;
+                self.filmstrip = None;
+                self.audio_tracks.clear();
+            }
             Message::OutputFolder(message) => {
                 self.output_folder = message;
             }
@@ -114,6 +583,29 @@ impl App {
                     self.output_folder = path.to_string();
                 }
             }
+            Message::Template(message) => {
+                self.template = message;
+                self.reset_missing_fighters();
+                self.reset_output_settings();
+            }
+            Message::OutputFormat(message) => {
+                self.output_format = message;
+            }
+            Message::Quality(message) => {
+                self.quality = message;
+            }
+            Message::MaxFileSize(message) => {
+                self.max_file_size = message;
+            }
+            Message::SetDefaultTemplate => {
+                if let Err(e) = img::set_default_template(&self.template) {
+                    return Task::done(Message::UpdateMsg(e));
+                }
+                return Task::done(Message::UpdateMsg(format!(
+                    "\"{}\" is now the default template",
+                    self.template
+                )));
+            }
             Message::TournamentName(message) => {
                 self.tournament_name = message;
             }
@@ -135,66 +627,28 @@ impl App {
             Message::Fighter2(message) => {
                 self.fighter_2 = message;
             }
+            Message::Score1(message) => {
+                self.score_1 = message;
+            }
+            Message::Score2(message) => {
+                self.score_2 = message;
+            }
             Message::StartTime(message) => {
                 self.start_time = message;
             }
             Message::EndTime(message) => {
                 self.end_time = message;
             }
+            Message::FrameTime(message) => {
+                self.frame_time = message;
+            }
             Message::Submit => {
                 let data = self.clone();
                 self.message = String::from("Working on it...");
                 return Task::future(async move {
-                    let msg = task::spawn_blocking(move || {
-                        let mut msg = String::from("Finished");
-                        if data.generate_thumbnail {
-                            let filename_png =
-                                PathBuf::from(&data.output_folder).join(get_filename(
-                                    &data.tournament_name,
-                                    &data.round_name,
-                                    &data.player_1,
-                                    &data.player_2,
-                                    "jpg",
-                                ));
-                            write_thumbnail(
-                                filename_png,
-                                &data.tournament_name,
-                                &data.round_name,
-                                &data.date,
-                                &data.player_1,
-                                &data.fighter_1,
-                                &data.player_2,
-                                &data.fighter_2,
-                            );
-                            msg.push_str(" generating thumbnail");
-                            if data.generate_video {
-                                msg.push_str(" and");
-                            }
-                        }
-
-                        if data.generate_video {
-                            let filename_mp4 =
-                                PathBuf::from(&data.output_folder).join(get_filename(
-                                    &data.tournament_name,
-                                    &data.round_name,
-                                    &data.player_1,
-                                    &data.player_2,
-                                    "mp4",
-                                ));
-                            trim_video(
-                                "static/ffmpeg.exe",
-                                &data.input_file,
-                                filename_mp4.to_str().expect("Invalid filename"),
-                                &data.start_time,
-                                &data.end_time,
-                            );
-                            msg.push_str(" generating video");
-                        }
-                        msg.push('!');
-                        msg
-                    })
-                    .await
-                    .unwrap();
+                    let msg = task::spawn_blocking(move || data.run_job().unwrap_or_else(|e| e))
+                        .await
+                        .unwrap();
                     Message::UpdateMsg(msg)
                 });
             }
@@ -204,58 +658,324 @@ impl App {
             }
             Message::GenerateThumbnail(message) => self.generate_thumbnail = message,
             Message::GenerateVideo(message) => self.generate_video = message,
+            Message::TeamMode(message) => self.team_mode = message,
             Message::ReloadConfig => {
-                crate::img::reload_config();
+                if let Err(e) = self.reload_config() {
+                    return Task::done(Message::UpdateMsg(e));
+                }
+            }
+            Message::OpenEditor => match Editor::open(&self.template, self.set_info()) {
+                Ok((editor, render)) => {
+                    self.editor = Some(editor);
+                    return render;
+                }
+                Err(e) => return Task::done(Message::UpdateMsg(e)),
+            },
+            Message::Editor(message) => {
+                if let Some(editor) = &mut self.editor {
+                    return editor.update(message);
+                }
+                return Task::none();
+            }
+            Message::SaveTemplate => {
+                let Some(editor) = &self.editor else {
+                    return Task::none();
+                };
+                let saved = editor
+                    .save()
+                    .map(|()| format!("Saved {}", editor.path().display()));
+                let result = saved.and_then(|msg| self.reload_config().map(|()| msg));
+                if let Some(editor) = &mut self.editor {
+                    editor.message = result.unwrap_or_else(|e| e);
+                }
+                return Task::none();
+            }
+            Message::CloseEditor => self.editor = None,
+            Message::LoadPreview => {
+                let files = self.input_files.clone();
+                self.message = String::from("Loading preview...");
+                return Task::future(async move {
+                    let scrubber = task::spawn_blocking(move || Scrubber::load(&files))
+                        .await
+                        .unwrap();
+                    Message::PreviewLoaded(scrubber)
+                });
+            }
+            Message::PreviewLoaded(result) => match result {
+                Ok(scrubber) => {
+                    self.audio_tracks = audio::rows(scrubber.timeline.audio());
+                    self.scrubber = Some(scrubber);
+                    self.message.clear();
+                    return self.decode_preview();
+                }
+                Err(e) => return Task::done(Message::UpdateMsg(e)),
+            },
+            Message::SeekPreview(position) => {
+                if let Some(scrubber) = &mut self.scrubber {
+                    scrubber.seek(position);
+                }
+                return Task::none();
+            }
+            Message::DecodePreview => return self.decode_preview(),
+            Message::StepPreview(seconds) => {
+                if let Some(scrubber) = &mut self.scrubber {
+                    scrubber.step(seconds);
+                }
+                return self.decode_preview();
+            }
+            Message::PreviewFrame(position, frame) => match frame {
+                Ok(frame) => {
+                    if let Some(scrubber) = &mut self.scrubber {
+                        scrubber.show_frame(position, frame);
+                    }
+                    return Task::none();
+                }
+                Err(e) => return Task::done(Message::UpdateMsg(e)),
+            },
+            Message::SetStartHere => {
+                if let Some(scrubber) = &self.scrubber {
+                    self.start_time = scrubber.timestamp();
+                }
+            }
+            Message::SetEndHere => {
+                if let Some(scrubber) = &self.scrubber {
+                    self.end_time = scrubber.timestamp();
+                }
+            }
+            Message::FilmstripInterval(message) => self.filmstrip_interval = message,
+            Message::BuildFilmstrip => {
+                let Ok(interval) = self.filmstrip_interval.trim().parse::<u32>() else {
+                    return Task::done(Message::UpdateMsg(String::from(
+                        "Filmstrip interval must be a whole number of seconds",
+                    )));
+                };
+                let files = self.input_files.clone();
+                self.message = String::from("Building filmstrip...");
+                let build = Task::future(async move {
+                    let inputs = files.clone();
+                    let filmstrip = task::spawn_blocking(move || {
+                        Filmstrip::build(&Timeline::probe(&inputs)?, interval)
+                    })
+                    .await
+                    .unwrap();
+                    Message::FilmstripBuilt(files, filmstrip)
+                });
+                // Tiles jump the preview, so make sure there is one.
+                if self.scrubber.is_none() {
+                    return Task::batch([Task::done(Message::LoadPreview), build]);
+                }
+                return build;
+            }
+            // The input changed while the filmstrip was building, so it's for the wrong video.
+            Message::FilmstripBuilt(files, _) if files != self.input_files => {}
+            Message::FilmstripBuilt(_, result) => match result {
+                Ok(filmstrip) => {
+                    self.filmstrip = Some(filmstrip);
+                    self.message.clear();
+                }
+                Err(e) => return Task::done(Message::UpdateMsg(e)),
+            },
+            Message::AddToBatch => {
+                match self.batch_segment(self.start_time.clone(), self.end_time.clone()) {
+                    Ok(segment) => self.batch.push(segment),
+                    Err(e) => return Task::done(Message::UpdateMsg(e)),
+                }
+            }
+            Message::DetectSets => {
+                let files = self.input_files.clone();
+                self.message = String::from("Analyzing the video...");
+                return Task::future(async move {
+                    let sets = task::spawn_blocking(move || {
+                        let settings = settings::load_settings()?;
+                        detect::detect_sets(&Timeline::probe(&files)?, &settings.detection)
+                    })
+                    .await
+                    .unwrap();
+                    Message::SetsDetected(sets)
+                });
+            }
+            Message::SetsDetected(result) => match result {
+                Ok(sets) => {
+                    for (start, end) in &sets {
+                        match self.detected_segment(*start, *end) {
+                            Ok(segment) => self.batch.push(segment),
+                            Err(e) => return Task::done(Message::UpdateMsg(e)),
+                        }
+                    }
+                    return Task::done(Message::UpdateMsg(format!(
+                        "Found {} possible sets",
+                        sets.len()
+                    )));
+                }
+                Err(e) => return Task::done(Message::UpdateMsg(e)),
+            },
+            Message::TrimBatch => {
+                let data = self.clone();
+                self.message = String::from("Working on it...");
+                return Task::future(async move {
+                    let msg = task::spawn_blocking(move || data.run_batch().unwrap_or_else(|e| e))
+                        .await
+                        .unwrap();
+                    Message::UpdateMsg(msg)
+                });
+            }
+            Message::Batch(message) => {
+                let renamed = match &message {
+                    // Filenames come from the template's pattern, so follow the new one.
+                    BatchMessage::Template(index, _) => Some(*index),
+                    // Follow the details too, unless the filename has been edited by hand.
+                    BatchMessage::Detail(index, ..) => self
+                        .batch_row_name(*index)
+                        .is_ok_and(|name| name == self.batch[*index].name)
+                        .then_some(*index),
+                    _ => None,
+                };
+                batch::update(&mut self.batch, message);
+                if let Some(index) = renamed {
+                    match self.batch_row_name(index) {
+                        Ok(name) => self.batch[index].name = name,
+                        Err(e) => return Task::done(Message::UpdateMsg(e)),
+                    }
+                }
+            }
+            Message::Audio(message) => audio::update(&mut self.audio_tracks, message),
+            Message::JumpTo(position) => {
+                if let Some(scrubber) = &mut self.scrubber {
+                    scrubber.seek(position);
+                    self.message = format!(
+                        "Use Set Start Here or Set End Here to use {}",
+                        scrubber.timestamp()
+                    );
+                }
+                return self.decode_preview();
             }
         }
         Task::done(Message::UpdateMsg(String::new()))
     }
 
     fn view(&self) -> Element<'_, Message> {
-        let char_imgs = CHAR_IMGS.read().expect("Poisoned!").clone();
-        Column::new()
+        if let Some(editor) = &self.editor {
+            return editor.view();
+        }
+        let char_imgs = img::characters(&self.template);
+        let input_rows = self.input_files.iter().enumerate().map(|(index, file)| {
+            let row = Row::new()
+                .spacing(5)
+                .align_y(iced::alignment::Vertical::Center)
+                .padding(iced::Padding::new(10.0).top(if index == 0 { 10.0 } else { 0.0 }))
+                .push(
+                    Text::new(if index == 0 { "Input File:" } else { "" })
+                        .width(iced::Length::FillPortion(1))
+                        .align_x(iced::alignment::Horizontal::Left),
+                )
+                .push(
+                    TextInput::new("", file)
+                        .width(iced::Length::FillPortion(5))
+                        .align_x(iced::alignment::Horizontal::Left)
+                        .on_input(move |file| Message::InputFile(index, file)),
+                );
+            let row = if index == 0 {
+                row.push(
+                    Button::new(Text::new("Browse..."))
+                        .width(100.0)
+                        .on_press(Message::BrowseFile),
+                )
+                .push(
+                    Button::new(Text::new("Add File..."))
+                        .width(100.0)
+                        .on_press(Message::AddInputFile),
+                )
+                .push(
+                    Button::new(Text::new("Preview"))
+                        .width(100.0)
+                        .on_press(Message::LoadPreview),
+                )
+            } else {
+                row.push(
+                    Button::new(Text::new("Remove"))
+                        .width(100.0)
+                        .on_press(Message::RemoveInputFile(index)),
+                )
+            };
+            row.into()
+        });
+        let form = Column::new()
+            .extend(input_rows)
             .push(
                 Row::new()
                     .spacing(5)
                     .align_y(iced::alignment::Vertical::Center)
                     .padding(iced::Padding::new(10.0).top(10.0))
                     .push(
-                        Text::new("Input File:")
+                        Text::new("Output Folder:")
                             .width(iced::Length::FillPortion(1))
                             .align_x(iced::alignment::Horizontal::Left),
                     )
                     .push(
-                        TextInput::new("", &self.input_file)
+                        TextInput::new("", &self.output_folder)
                             .width(iced::Length::FillPortion(5))
                             .align_x(iced::alignment::Horizontal::Left)
-                            .on_input(Message::InputFile),
+                            .on_input(Message::OutputFolder),
                     )
                     .push(
                         Button::new(Text::new("Browse..."))
                             .width(100.0)
-                            .on_press(Message::BrowseFile),
+                            .on_press(Message::BrowserFolder),
                     ),
             )
             .push(
                 Row::new()
                     .spacing(5)
                     .align_y(iced::alignment::Vertical::Center)
-                    .padding(iced::Padding::new(10.0).top(10.0))
+                    .padding(iced::Padding::new(10.0).top(0.0))
                     .push(
-                        Text::new("Output Folder:")
+                        Text::new("Template:")
                             .width(iced::Length::FillPortion(1))
                             .align_x(iced::alignment::Horizontal::Left),
                     )
                     .push(
-                        TextInput::new("", &self.output_folder)
-                            .width(iced::Length::FillPortion(5))
-                            .align_x(iced::alignment::Horizontal::Left)
-                            .on_input(Message::OutputFolder),
+                        pick_list(
+                            img::template_names(),
+                            Some(&self.template),
+                            Message::Template,
+                        )
+                        .width(iced::Length::FillPortion(5)),
                     )
                     .push(
-                        Button::new(Text::new("Browse..."))
+                        Button::new(Text::new("Set as Default"))
                             .width(100.0)
-                            .on_press(Message::BrowserFolder),
+                            .on_press(Message::SetDefaultTemplate),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_y(iced::alignment::Vertical::Center)
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(
+                        Text::new("Thumbnail Format:")
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left),
+                    )
+                    .push(
+                        pick_list(
+                            OutputFormat::ALL,
+                            Some(self.output_format),
+                            Message::OutputFormat,
+                        )
+                        .width(iced::Length::FillPortion(1)),
+                    )
+                    .push(
+                        TextInput::new("JPEG Quality", &self.quality)
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left)
+                            .on_input(Message::Quality),
+                    )
+                    .push(
+                        TextInput::new("Max Size (KB)", &self.max_file_size)
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left)
+                            .on_input(Message::MaxFileSize),
                     ),
             )
             .push(
@@ -321,10 +1041,16 @@ impl App {
                     )
                     .push(
                         TextInput::new("", &self.player_1)
-                            .width(iced::Length::FillPortion(4))
+                            .width(iced::Length::FillPortion(3))
                             .align_x(iced::alignment::Horizontal::Left)
                             .on_input(Message::Player1),
                     )
+                    .push(
+                        TextInput::new("Score", &self.score_1)
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left)
+                            .on_input(Message::Score1),
+                    )
                     .push(
                         pick_list(char_imgs.clone(), Some(&self.fighter_1), Message::Fighter1)
                             .width(iced::Length::FillPortion(1)),
@@ -342,10 +1068,16 @@ impl App {
                     )
                     .push(
                         TextInput::new("", &self.player_2)
-                            .width(iced::Length::FillPortion(4))
+                            .width(iced::Length::FillPortion(3))
                             .align_x(iced::alignment::Horizontal::Left)
                             .on_input(Message::Player2),
                     )
+                    .push(
+                        TextInput::new("Score", &self.score_2)
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left)
+                            .on_input(Message::Score2),
+                    )
                     .push(
                         pick_list(char_imgs.clone(), Some(&self.fighter_2), Message::Fighter2)
                             .width(iced::Length::FillPortion(1)),
@@ -374,6 +1106,92 @@ impl App {
                             .on_input(Message::EndTime),
                     ),
             )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_y(iced::alignment::Vertical::Center)
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(
+                        Text::new("Batch:")
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left),
+                    )
+                    .push(
+                        Button::new(Text::new("Add to Batch"))
+                            .width(iced::Length::FillPortion(1))
+                            .on_press(Message::AddToBatch),
+                    )
+                    .push(
+                        Button::new(Text::new("Detect Sets"))
+                            .width(iced::Length::FillPortion(1))
+                            .on_press(Message::DetectSets),
+                    )
+                    .push(
+                        Button::new(Text::new("Trim Batch"))
+                            .width(iced::Length::FillPortion(1))
+                            .on_press_maybe((!self.batch.is_empty()).then_some(Message::TrimBatch)),
+                    ),
+            )
+            .push(batch::view(&self.batch))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_y(iced::alignment::Vertical::Center)
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(
+                        Text::new("Filmstrip Interval (seconds):")
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left),
+                    )
+                    .push(
+                        TextInput::new("", &self.filmstrip_interval)
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left)
+                            .on_input(Message::FilmstripInterval),
+                    )
+                    .push(
+                        Button::new(Text::new("Build Filmstrip"))
+                            .width(iced::Length::FillPortion(1))
+                            .on_press(Message::BuildFilmstrip),
+                    ),
+            )
+            .push_maybe(self.filmstrip.as_ref().map(|filmstrip| {
+                Row::new()
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(filmstrip.view(self.scrubber.as_ref().map(|s| s.position)))
+            }))
+            .push_maybe(self.scrubber.as_ref().map(Scrubber::view))
+            .push_maybe((!self.audio_tracks.is_empty()).then(|| {
+                Row::new()
+                    .spacing(5)
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(
+                        Text::new("Audio Tracks:")
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left),
+                    )
+                    .push(
+                        iced::widget::container(audio::view(&self.audio_tracks))
+                            .width(iced::Length::FillPortion(3)),
+                    )
+            }))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_y(iced::alignment::Vertical::Center)
+                    .padding(iced::Padding::new(10.0).top(0.0))
+                    .push(
+                        Text::new("Thumbnail Frame Time (HH:MM:SS):")
+                            .width(iced::Length::FillPortion(1))
+                            .align_x(iced::alignment::Horizontal::Left),
+                    )
+                    .push(
+                        TextInput::new("Middle of the set", &self.frame_time)
+                            .width(iced::Length::FillPortion(2))
+                            .align_x(iced::alignment::Horizontal::Left)
+                            .on_input(Message::FrameTime),
+                    ),
+            )
             .push(
                 Row::new()
                     .spacing(5)
@@ -388,6 +1206,11 @@ impl App {
                         Checkbox::new("Generate Video", self.generate_video)
                             .on_toggle(Message::GenerateVideo)
                             .width(iced::Length::FillPortion(1)),
+                    )
+                    .push(
+                        Checkbox::new("Team Mode", self.team_mode)
+                            .on_toggle(Message::TeamMode)
+                            .width(iced::Length::FillPortion(1)),
                     ),
             )
             .push(
@@ -405,12 +1228,17 @@ impl App {
                             .width(iced::Length::FillPortion(1))
                             .align_x(iced::alignment::Horizontal::Center),
                     )
+                    .push(
+                        Button::new(Text::new("Edit Layout"))
+                            .width(200.0)
+                            .on_press(Message::OpenEditor),
+                    )
                     .push(
                         Button::new(Text::new("Reload Config"))
                             .width(200.0)
                             .on_press(Message::ReloadConfig),
                     ),
-            )
-            .into()
+            );
+        scrollable(form).into()
     }
 }
@@ -0,0 +1,106 @@
+use iced::widget::image::Handle;
+use iced::widget::{image, slider, Button, Column, Row, Text};
+use iced::Element;
+
+use crate::timeline::Timeline;
+use crate::video::{extract_frame, format_timestamp, FFMPEG_PATH};
+use crate::Message;
+
+/// Width the preview frames are decoded at; small enough to decode quickly while scrubbing.
+const PREVIEW_WIDTH: u32 = 640;
+const PREVIEW_HEIGHT: f32 = 270.0;
+
+/// A preview of the input video with a playhead that can be moved around.
+#[derive(Clone, Debug)]
+pub struct Scrubber {
+    pub timeline: Timeline,
+    /// Playhead position, in seconds.
+    pub position: f64,
+    frame: Option<Handle>,
+}
+
+impl Scrubber {
+    /// Probes the input files so they can be previewed.
+    pub fn load(files: &[String]) -> Result<Scrubber, String> {
+        Ok(Scrubber {
+            timeline: Timeline::probe(files)?,
+            position: 0.0,
+            frame: None,
+        })
+    }
+
+    /// Moves the playhead, staying within the video.
+    pub fn seek(&mut self, position: f64) {
+        self.position = position.clamp(0.0, self.timeline.duration());
+    }
+
+    /// Moves the playhead by `seconds`, which may be negative.
+    pub fn step(&mut self, seconds: f64) {
+        self.seek(self.position + seconds);
+    }
+
+    /// Shows a decoded frame, unless the playhead has moved on since it was requested.
+    pub fn show_frame(&mut self, position: f64, frame: Handle) {
+        if position == self.position {
+            self.frame = Some(frame);
+        }
+    }
+
+    pub fn timestamp(&self) -> String {
+        format_timestamp(self.position)
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let frame: Element<'_, Message> = match &self.frame {
+            Some(frame) => image(frame.clone()).height(PREVIEW_HEIGHT).into(),
+            None => Text::new("Decoding...").height(PREVIEW_HEIGHT).into(),
+        };
+        let step = |label: &'static str, seconds: f64| {
+            Button::new(Text::new(label)).on_press(Message::StepPreview(seconds))
+        };
+        let frame_length = 1.0 / self.timeline.fps();
+        Column::new()
+            .spacing(5)
+            .align_x(iced::alignment::Horizontal::Center)
+            .padding(iced::Padding::new(10.0).top(0.0))
+            .push(frame)
+            .push(
+                slider(
+                    0.0..=self.timeline.duration(),
+                    self.position,
+                    Message::SeekPreview,
+                )
+                .step(frame_length)
+                .on_release(Message::DecodePreview),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_y(iced::alignment::Vertical::Center)
+                    .push(step("-1s", -1.0))
+                    .push(step("-1 frame", -frame_length))
+                    .push(
+                        Text::new(self.timestamp())
+                            .width(iced::Length::Fill)
+                            .align_x(iced::alignment::Horizontal::Center),
+                    )
+                    .push(step("+1 frame", frame_length))
+                    .push(step("+1s", 1.0))
+                    .push(Button::new(Text::new("Set Start Here")).on_press(Message::SetStartHere))
+                    .push(Button::new(Text::new("Set End Here")).on_press(Message::SetEndHere)),
+            )
+            .into()
+    }
+}
+
+/// Decodes the frame at `position` seconds on the timeline for the preview.
+pub fn decode_frame(timeline: &Timeline, position: f64) -> Result<Handle, String> {
+    let (input, time) = timeline.locate(position);
+    let png = extract_frame(
+        FFMPEG_PATH,
+        input,
+        &format_timestamp(time),
+        Some(PREVIEW_WIDTH),
+    )?;
+    Ok(Handle::from_bytes(png))
+}
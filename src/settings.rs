@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::detect::DetectionSettings;
+
+const SETTINGS_PATH: &str = "static/settings.json";
+
+/// User preferences that persist between runs, stored next to the templates.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_template: Option<String>,
+    #[serde(default)]
+    pub detection: DetectionSettings,
+}
+
+/// Reads `static/settings.json`, falling back to defaults if it doesn't exist.
+pub fn load_settings() -> Result<Settings, String> {
+    let path = Path::new(SETTINGS_PATH);
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let data =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read settings.json: {e}"))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse settings.json: {e}"))
+}
+
+pub fn save_settings(settings: &Settings) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(settings).expect("Settings are always serializable");
+    fs::write(SETTINGS_PATH, data).map_err(|e| format!("Failed to write settings.json: {e}"))
+}
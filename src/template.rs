@@ -0,0 +1,510 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use std::fmt::Write;
+
+/// Formats accepted when parsing the date typed into the GUI for `{date:...}`.
+const DATE_INPUT_FORMATS: [&str; 6] = [
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%m/%d/%Y",
+    "%B %d, %Y",
+    "%b %d, %Y",
+    "%d %B %Y",
+];
+
+const TOKENS: [&str; 13] = [
+    "tournament",
+    "round",
+    "date",
+    "player1",
+    "player2",
+    "fighter1",
+    "fighter2",
+    "score1",
+    "score2",
+    "winner",
+    "loser",
+    "fighter1.accent",
+    "fighter2.accent",
+];
+
+/// Whole-text placeholders from before tokens existed, with the token each one stands for.
+const LEGACY_PLACEHOLDERS: [(&str, &str); 5] = [
+    ("TOURNAMENT_NAME", "{tournament}"),
+    ("ROUND_NAME", "{round}"),
+    ("DATE", "{date}"),
+    ("PLAYER_1", "{player1}"),
+    ("PLAYER_2", "{player2}"),
+];
+
+/// The user's entries for a single set, used to fill in template tokens.
+#[derive(Clone, Debug, Default)]
+pub struct SetInfo {
+    pub tournament_name: String,
+    pub round_name: String,
+    pub date: String,
+    pub player_1: String,
+    pub fighter_1: String,
+    pub player_2: String,
+    pub fighter_2: String,
+    pub score_1: String,
+    pub score_2: String,
+    pub team_mode: bool,
+    /// Accent colors of the two characters, filled in from character metadata when rendering.
+    pub accent_1: String,
+    pub accent_2: String,
+}
+
+impl SetInfo {
+    /// The side (1 or 2) with the higher score, if both scores are numbers and differ.
+    pub fn winner(&self) -> Option<u8> {
+        let score_1: u32 = self.score_1.trim().parse().ok()?;
+        let score_2: u32 = self.score_2.trim().parse().ok()?;
+        match score_1.cmp(&score_2) {
+            std::cmp::Ordering::Greater => Some(1),
+            std::cmp::Ordering::Less => Some(2),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    /// The side (1 or 2) with the lower score, if there is a winner.
+    pub fn loser(&self) -> Option<u8> {
+        self.winner().map(|side| 3 - side)
+    }
+
+    fn player(&self, side: Option<u8>) -> String {
+        match side {
+            Some(1) => self.player_1.clone(),
+            Some(2) => self.player_2.clone(),
+            _ => String::new(),
+        }
+    }
+
+    pub fn is_token(name: &str) -> bool {
+        TOKENS.contains(&name)
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "tournament" => self.tournament_name.clone(),
+            "round" => self.round_name.clone(),
+            "date" => self.date.clone(),
+            "player1" => self.player_1.clone(),
+            "player2" => self.player_2.clone(),
+            "fighter1" => fighter_name(&self.fighter_1),
+            "fighter2" => fighter_name(&self.fighter_2),
+            "score1" => self.score_1.trim().to_string(),
+            "score2" => self.score_2.trim().to_string(),
+            "winner" => self.player(self.winner()),
+            "loser" => self.player(self.loser()),
+            "fighter1.accent" => self.accent_1.clone(),
+            "fighter2.accent" => self.accent_2.clone(),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Segment {
+    Literal(String),
+    Token(Token),
+}
+
+#[derive(Debug)]
+struct Token {
+    name: String,
+    format: Option<String>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug)]
+enum Filter {
+    Upper,
+    Lower,
+    Title,
+    Trim,
+    Truncate(usize),
+    Default(String),
+    Prefix(String),
+    Suffix(String),
+}
+
+/// The token for a text that is exactly one of the old placeholders such as `PLAYER_1`, so
+/// configs written before tokens keep working.
+pub fn legacy_token(text: &str) -> Option<&'static str> {
+    LEGACY_PLACEHOLDERS
+        .iter()
+        .find(|(placeholder, _)| *placeholder == text)
+        .map(|(_, token)| *token)
+}
+
+/// Checks that a template only uses known tokens, filters and date formats.
+pub fn validate(template: &str) -> Result<(), String> {
+    parse(template).map(|_| ())
+}
+
+/// Replaces every `{token|filter...}` in `template` with the matching value from `info`.
+///
+/// `{{` and `}}` produce literal braces.
+pub fn render(template: &str, info: &SetInfo) -> Result<String, String> {
+    let mut out = String::new();
+    for segment in parse(template)? {
+        match segment {
+            Segment::Literal(text) => out.push_str(&text),
+            Segment::Token(token) => out.push_str(&token.evaluate(info)?),
+        }
+    }
+    Ok(out)
+}
+
+fn parse(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut body = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => body.push(c),
+                        None => return Err(format!("Unclosed token in \"{template}\"")),
+                    }
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Token(
+                    parse_token(&body).map_err(|e| format!("{e} in \"{template}\""))?,
+                ));
+            }
+            '}' => return Err(format!("Unmatched '}}' in \"{template}\"")),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+fn parse_token(body: &str) -> Result<Token, String> {
+    let mut parts = body.split('|');
+    let head = parts.next().unwrap_or_default();
+    let (name, format) = match head.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format.to_string())),
+        None => (head.trim(), None),
+    };
+    if !SetInfo::is_token(name) {
+        return Err(format!("Unknown token {{{name}}}"));
+    }
+    if let Some(format) = &format {
+        if name != "date" {
+            return Err(format!("Token {{{name}}} does not accept a format"));
+        }
+        if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            return Err(format!("Invalid date format \"{format}\""));
+        }
+        // Dates typed into the GUI have no time of day to fill in `%H` and the like.
+        if write_date(NaiveDate::default(), format).is_err() {
+            return Err(format!(
+                "Date format \"{format}\" can only use date fields, not times"
+            ));
+        }
+    }
+    let filters = parts.map(parse_filter).collect::<Result<_, _>>()?;
+    Ok(Token {
+        name: name.to_string(),
+        format,
+        filters,
+    })
+}
+
+fn parse_filter(filter: &str) -> Result<Filter, String> {
+    let (name, arg) = match filter.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(arg)),
+        None => (filter.trim(), None),
+    };
+    match (name, arg) {
+        ("upper", None) => Ok(Filter::Upper),
+        ("lower", None) => Ok(Filter::Lower),
+        ("title", None) => Ok(Filter::Title),
+        ("trim", None) => Ok(Filter::Trim),
+        ("trunc", Some(len)) => len
+            .trim()
+            .parse()
+            .map(Filter::Truncate)
+            .map_err(|_| format!("Invalid length \"{len}\" for filter trunc")),
+        ("default", Some(text)) => Ok(Filter::Default(text.to_string())),
+        ("prefix", Some(text)) => Ok(Filter::Prefix(text.to_string())),
+        ("suffix", Some(text)) => Ok(Filter::Suffix(text.to_string())),
+        _ => Err(format!("Unknown filter \"{filter}\"")),
+    }
+}
+
+impl Token {
+    fn evaluate(&self, info: &SetInfo) -> Result<String, String> {
+        let mut value = info
+            .lookup(&self.name)
+            .expect("Unknown token passed validation");
+        if let Some(format) = &self.format {
+            value = format_date(&value, format)?;
+        }
+        for filter in &self.filters {
+            value = match filter {
+                Filter::Upper => value.to_uppercase(),
+                Filter::Lower => value.to_lowercase(),
+                Filter::Title => title_case(&value),
+                Filter::Trim => value.trim().to_string(),
+                Filter::Truncate(len) => value.chars().take(*len).collect(),
+                Filter::Default(text) if value.is_empty() => text.clone(),
+                Filter::Default(_) => value,
+                Filter::Prefix(text) if !value.is_empty() => format!("{text}{value}"),
+                Filter::Suffix(text) if !value.is_empty() => format!("{value}{text}"),
+                Filter::Prefix(_) | Filter::Suffix(_) => value,
+            };
+        }
+        Ok(value)
+    }
+}
+
+/// Reformats a date typed into the GUI, leaving it untouched if it can't be understood.
+fn format_date(date: &str, format: &str) -> Result<String, String> {
+    match DATE_INPUT_FORMATS
+        .iter()
+        .find_map(|input| NaiveDate::parse_from_str(date.trim(), input).ok())
+    {
+        Some(parsed) => write_date(parsed, format),
+        None => Ok(date.to_string()),
+    }
+}
+
+/// Formats `date`, reporting an error rather than panicking like `to_string` does when the
+/// format needs fields a date doesn't have.
+fn write_date(date: NaiveDate, format: &str) -> Result<String, String> {
+    let mut out = String::new();
+    write!(out, "{}", date.format(format))
+        .map_err(|_| format!("Could not format {date} as \"{format}\""))?;
+    Ok(out)
+}
+
+pub fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut start_of_word = true;
+    for c in text.chars() {
+        if start_of_word {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        start_of_word = c.is_whitespace() || c == '-';
+    }
+    out
+}
+
+/// Character images are stored as files, so strip the extension for display.
+fn fighter_name(filename: &str) -> String {
+    std::path::Path::new(filename)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> SetInfo {
+        SetInfo {
+            tournament_name: String::from("Big House"),
+            round_name: String::new(),
+            date: String::from("2025-03-14"),
+            player_1: String::from("Mickey"),
+            fighter_1: String::from("Mario.png"),
+            player_2: String::from("Popeye"),
+            fighter_2: String::from("Link.png"),
+            score_1: String::from("3"),
+            score_2: String::from("1"),
+            ..SetInfo::default()
+        }
+    }
+
+    fn render_with(template: &str, info: &SetInfo) -> String {
+        render(template, info).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_replaced() {
+        assert_eq!(
+            render_with("{tournament}: {player1} ({fighter1}) vs {player2}", &info()),
+            "Big House: Mickey (Mario) vs Popeye"
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render_with("{{{player1}}}", &info()), "{Mickey}");
+    }
+
+    #[test]
+    fn unclosed_token_is_an_error() {
+        assert!(render("{player1", &info())
+            .unwrap_err()
+            .contains("Unclosed"));
+    }
+
+    #[test]
+    fn unmatched_closing_brace_is_an_error() {
+        assert!(render("player1}", &info())
+            .unwrap_err()
+            .contains("Unmatched"));
+    }
+
+    #[test]
+    fn unknown_token_is_an_error() {
+        assert!(validate("{player3}").unwrap_err().contains("Unknown token"));
+    }
+
+    #[test]
+    fn unknown_filter_is_an_error() {
+        assert!(validate("{player1|shout}")
+            .unwrap_err()
+            .contains("Unknown filter"));
+        // Filters that need an argument are unknown without one.
+        assert!(validate("{player1|trunc}").is_err());
+    }
+
+    #[test]
+    fn case_filters() {
+        assert_eq!(
+            render_with("{player1|upper} {player2|lower}", &info()),
+            "MICKEY popeye"
+        );
+        assert_eq!(
+            title_case("grand FINALS-losers side"),
+            "Grand Finals-Losers Side"
+        );
+    }
+
+    #[test]
+    fn trunc_keeps_the_first_characters() {
+        assert_eq!(render_with("{tournament|trunc:3}", &info()), "Big");
+        assert_eq!(render_with("{tournament|trunc: 20 }", &info()), "Big House");
+        assert!(validate("{tournament|trunc:x}")
+            .unwrap_err()
+            .contains("Invalid length"));
+    }
+
+    #[test]
+    fn filters_apply_in_order() {
+        assert_eq!(
+            render_with("{tournament|trunc:3|upper|suffix:!}", &info()),
+            "BIG!"
+        );
+    }
+
+    #[test]
+    fn default_only_fills_empty_values() {
+        assert_eq!(render_with("{round|default:Pools}", &info()), "Pools");
+        assert_eq!(render_with("{player1|default:TBD}", &info()), "Mickey");
+    }
+
+    #[test]
+    fn prefix_and_suffix_skip_empty_values() {
+        assert_eq!(render_with("{round|prefix:(|suffix:)}", &info()), "");
+        assert_eq!(
+            render_with("{player1|prefix:(|suffix:)}", &info()),
+            "(Mickey)"
+        );
+    }
+
+    #[test]
+    fn date_is_reformatted() {
+        assert_eq!(render_with("{date:%b %d, %Y}", &info()), "Mar 14, 2025");
+        let info = SetInfo {
+            date: String::from("March 14, 2025"),
+            ..info()
+        };
+        assert_eq!(render_with("{date:%Y/%m/%d}", &info), "2025/03/14");
+    }
+
+    #[test]
+    fn unparseable_date_is_kept_as_typed() {
+        let info = SetInfo {
+            date: String::from("Spring 2025"),
+            ..info()
+        };
+        assert_eq!(render_with("{date:%Y}", &info), "Spring 2025");
+    }
+
+    #[test]
+    fn date_format_with_time_fields_is_an_error() {
+        assert!(validate("{date:%H:%M}")
+            .unwrap_err()
+            .contains("only use date fields"));
+        assert!(validate("{date:%Q}")
+            .unwrap_err()
+            .contains("Invalid date format"));
+    }
+
+    #[test]
+    fn only_date_accepts_a_format() {
+        assert!(validate("{player1:%Y}")
+            .unwrap_err()
+            .contains("does not accept a format"));
+    }
+
+    #[test]
+    fn winner_and_loser() {
+        assert_eq!(
+            render_with("{winner} beat {loser}", &info()),
+            "Mickey beat Popeye"
+        );
+        let info = SetInfo {
+            score_1: String::from(" 1 "),
+            score_2: String::from("2"),
+            ..info()
+        };
+        assert_eq!(
+            render_with("{winner} beat {loser}", &info),
+            "Popeye beat Mickey"
+        );
+    }
+
+    #[test]
+    fn tied_scores_have_no_winner() {
+        let info = SetInfo {
+            score_2: String::from("3"),
+            ..info()
+        };
+        assert_eq!(info.winner(), None);
+        assert_eq!(render_with("{winner|default:Tied}{loser}", &info), "Tied");
+    }
+
+    #[test]
+    fn non_numeric_scores_have_no_winner() {
+        let info = SetInfo {
+            score_1: String::from("DQ"),
+            ..info()
+        };
+        assert_eq!(info.winner(), None);
+        assert_eq!(info.loser(), None);
+        assert_eq!(render_with("{winner}", &info), "");
+    }
+
+    #[test]
+    fn legacy_placeholders_become_tokens() {
+        assert_eq!(legacy_token("PLAYER_1"), Some("{player1}"));
+        assert_eq!(legacy_token("TOURNAMENT_NAME"), Some("{tournament}"));
+        assert_eq!(legacy_token("PLAYER_1 vs PLAYER_2"), None);
+    }
+}
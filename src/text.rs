@@ -0,0 +1,220 @@
+use ab_glyph::{point, Font, FontRef, GlyphId, OutlinedGlyph, PxScale, Rect, ScaleFont};
+use image::{Rgba, RgbaImage};
+use imageproc::pixelops::weighted_sum;
+use serde::Deserialize;
+
+use crate::effects::Gradient;
+use crate::template::title_case;
+
+/// Changes the case of a text before it is drawn.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TextTransform {
+    Upper,
+    Lower,
+    Title,
+}
+
+impl TextTransform {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            TextTransform::Upper => text.to_uppercase(),
+            TextTransform::Lower => text.to_lowercase(),
+            TextTransform::Title => title_case(text),
+        }
+    }
+}
+
+/// How the glyphs of a text are colored, with any tokens already filled in.
+#[derive(Clone, Debug)]
+pub struct TextFill {
+    pub color: Rgba<u8>,
+    /// Replaces `color` with a gradient across the text's bounding box.
+    pub gradient: Option<Gradient>,
+    /// Colors for the first visible characters, in order, overriding `color` and `gradient`.
+    pub char_colors: Vec<Rgba<u8>>,
+}
+
+/// Where each line sits horizontally within a multi-line text.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// The font and spacing used to lay out a text.
+pub struct TextStyle<'a> {
+    pub font: FontRef<'a>,
+    pub scale: PxScale,
+    /// Extra space between characters, in pixels.
+    pub letter_spacing: f32,
+    /// Distance between the tops of consecutive lines, as a multiple of the scale.
+    pub line_height: f32,
+    /// Lines longer than this many pixels are wrapped between words.
+    pub max_width: Option<u32>,
+    pub align: Align,
+}
+
+/// A text split into lines and positioned relative to the top left corner of the block.
+pub struct TextBlock {
+    lines: Vec<Line>,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct Line {
+    text: String,
+    x: i32,
+    y: i32,
+}
+
+impl TextStyle<'_> {
+    /// The width and height of a single line, like `imageproc::drawing::text_size` but
+    /// including letter spacing.
+    fn size(&self, text: &str) -> (u32, u32) {
+        self.layout_glyphs(text, |_, _, _| {})
+    }
+
+    /// Splits `text` into lines at `\n` and wherever it is wider than `max_width`,
+    /// and measures the whole block.
+    pub fn layout(&self, text: &str) -> TextBlock {
+        let lines: Vec<String> = text
+            .split('\n')
+            .flat_map(|paragraph| self.wrap(paragraph))
+            .collect();
+        let pitch = self.scale.y * self.line_height;
+        let sizes: Vec<(u32, u32)> = lines.iter().map(|line| self.size(line)).collect();
+        let width = sizes.iter().map(|size| size.0).max().unwrap_or(0);
+        let height = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| (i as f32 * pitch) as u32 + size.1)
+            .max()
+            .unwrap_or(0);
+        let lines = lines
+            .into_iter()
+            .zip(sizes)
+            .enumerate()
+            .map(|(i, (text, size))| Line {
+                text,
+                x: match self.align {
+                    Align::Left => 0,
+                    Align::Center => (width - size.0) as i32 / 2,
+                    Align::Right => (width - size.0) as i32,
+                },
+                y: (i as f32 * pitch) as i32,
+            })
+            .collect();
+        TextBlock {
+            lines,
+            width,
+            height,
+        }
+    }
+
+    /// Breaks `paragraph` between words so each line fits within `max_width` where possible.
+    fn wrap(&self, paragraph: &str) -> Vec<String> {
+        let Some(max_width) = self.max_width else {
+            return vec![paragraph.to_string()];
+        };
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if line.is_empty() || self.size(&candidate).0 <= max_width {
+                line = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            }
+        }
+        lines.push(line);
+        lines
+    }
+
+    /// Draws `block` with its top left corner at (`x`, `y`), like `imageproc::drawing::draw_text_mut`
+    /// but filling each glyph according to `fill`.
+    pub fn draw(
+        &self,
+        canvas: &mut RgbaImage,
+        fill: &TextFill,
+        x: i32,
+        y: i32,
+        block: &TextBlock,
+    ) -> Result<(), String> {
+        let (width, height) = (block.width, block.height);
+        let gradient = fill
+            .gradient
+            .as_ref()
+            .map(|gradient| gradient.sampler(width.max(1), height.max(1)))
+            .transpose()?;
+        let (canvas_width, canvas_height) = (canvas.width() as i32, canvas.height() as i32);
+        // Character colors carry on from one line to the next.
+        let mut first_index = 0;
+        for line in &block.lines {
+            let mut glyphs = 0;
+            self.layout_glyphs(&line.text, |index, glyph, bounds| {
+                glyphs += 1;
+                glyph.draw(|gx, gy, coverage| {
+                    let px = gx as i32 + x + line.x + bounds.min.x.round() as i32;
+                    let py = gy as i32 + y + line.y + bounds.min.y.round() as i32;
+                    if !(0..canvas_width).contains(&px) || !(0..canvas_height).contains(&py) {
+                        return;
+                    }
+                    let color = match (fill.char_colors.get(first_index + index), &gradient) {
+                        (Some(color), _) => *color,
+                        (None, Some(sampler)) => sampler(
+                            (px - x).clamp(0, width as i32) as u32,
+                            (py - y).clamp(0, height as i32) as u32,
+                        ),
+                        (None, None) => fill.color,
+                    };
+                    let coverage = coverage.clamp(0.0, 1.0);
+                    let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+                    *pixel = weighted_sum(*pixel, color, 1.0 - coverage, coverage);
+                })
+            });
+            first_index += glyphs;
+        }
+        Ok(())
+    }
+
+    /// Positions glyphs the same way as imageproc, passing each visible glyph's index
+    /// along with it, and returns the size of the text.
+    fn layout_glyphs(
+        &self,
+        text: &str,
+        mut f: impl FnMut(usize, OutlinedGlyph, Rect),
+    ) -> (u32, u32) {
+        let font = self.font.as_scaled(self.scale);
+        let (mut width, mut height) = (0f32, 0f32);
+        let mut last: Option<GlyphId> = None;
+        let mut index = 0;
+        for c in text.chars() {
+            let glyph_id = font.glyph_id(c);
+            let glyph = glyph_id.with_scale_and_position(self.scale, point(width, font.ascent()));
+            width += font.h_advance(glyph_id) + self.letter_spacing;
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                if let Some(last) = last {
+                    width += font.kern(glyph_id, last);
+                }
+                last = Some(glyph_id);
+                let bounds = outlined.px_bounds();
+                height = height.max(bounds.height());
+                f(index, outlined, bounds);
+                index += 1;
+            }
+        }
+        if !text.is_empty() {
+            // Spacing only goes between characters.
+            width -= self.letter_spacing;
+        }
+        (width.max(0.0) as u32, height as u32)
+    }
+}
@@ -0,0 +1,286 @@
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use crate::video::{
+    concat_videos, extract_frame, format_timestamp, probe_video, trim_segments, trim_video,
+    AudioStream, TrimOptions, VideoInfo, WorkDir, FFMPEG_PATH,
+};
+
+/// Ordered input files played back to back as one continuous video, for when the recording
+/// software rolls over to a new file partway through a set.
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    files: Vec<(String, VideoInfo)>,
+}
+
+/// Part of a trim that falls within a single input file, in that file's own times.
+struct Piece {
+    file: usize,
+    start: f64,
+    end: f64,
+}
+
+impl Timeline {
+    /// Probes every file for its length.
+    pub fn probe(files: &[String]) -> Result<Timeline, String> {
+        let files: Vec<String> = files
+            .iter()
+            .map(|file| file.trim().to_string())
+            .filter(|file| !file.is_empty())
+            .collect();
+        if files.is_empty() {
+            return Err(String::from("No input file selected"));
+        }
+        let files = files
+            .into_iter()
+            .map(|file| probe_video(FFMPEG_PATH, &file).map(|info| (file, info)))
+            .collect::<Result<_, String>>()?;
+        Ok(Timeline { files })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.files.iter().map(|(_, info)| info.duration).sum()
+    }
+
+    /// Frame rate of the first file, which the others are assumed to share.
+    pub fn fps(&self) -> f64 {
+        self.files[0].1.fps
+    }
+
+    /// Audio tracks of the first file, which the others are assumed to share.
+    pub fn audio(&self) -> &[AudioStream] {
+        &self.files[0].1.audio
+    }
+
+    /// Each file along with the time it starts at on the timeline.
+    pub fn files(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.file_details().map(|(file, _, start)| (file, start))
+    }
+
+    /// Each file with what probing found in it and the time it starts at on the timeline.
+    pub fn file_details(&self) -> impl Iterator<Item = (&str, &VideoInfo, f64)> {
+        self.files.iter().scan(0.0, |offset, (file, info)| {
+            let start = *offset;
+            *offset += info.duration;
+            Some((file.as_str(), info, start))
+        })
+    }
+
+    /// The file playing at `time` on the timeline, and the time within that file.
+    pub fn locate(&self, time: f64) -> (&str, f64) {
+        let mut offset = 0.0;
+        for (file, info) in &self.files {
+            if time < offset + info.duration {
+                return (file, (time - offset).max(0.0));
+            }
+            offset += info.duration;
+        }
+        // Past the end, so use the end of the last file.
+        let (file, info) = self.files.last().expect("A timeline has at least one file");
+        (file, info.duration)
+    }
+
+    /// Decodes the full-size frame at `time` on the timeline.
+    pub fn frame(&self, time: f64) -> Result<RgbaImage, String> {
+        let (input, file_time) = self.locate(time);
+        let time = format_timestamp(file_time);
+        let png = extract_frame(FFMPEG_PATH, input, &time, None)?;
+        image::load_from_memory(&png)
+            .map(|frame| frame.to_rgba8())
+            .map_err(|e| format!("Could not decode the frame at {time}: {e}"))
+    }
+
+    /// Splits `start` to `end` on the timeline into the parts that fall within each file.
+    fn pieces(&self, start: f64, end: f64) -> Vec<Piece> {
+        self.files()
+            .zip(&self.files)
+            .enumerate()
+            .filter_map(|(index, ((_, offset), (_, info)))| {
+                let piece_start = (start - offset).max(0.0);
+                let piece_end = (end - offset).min(info.duration);
+                (piece_start < piece_end).then_some(Piece {
+                    file: index,
+                    start: piece_start,
+                    end: piece_end,
+                })
+            })
+            .collect()
+    }
+
+    /// Cuts `start` to `end` on the timeline out to `output`.
+    pub fn trim(
+        &self,
+        output: &Path,
+        start: f64,
+        end: f64,
+        options: &TrimOptions,
+    ) -> Result<(), String> {
+        self.trim_all(&[(start, end, output.to_path_buf())], options)
+    }
+
+    /// Cuts each `(start, end, output)` range out of the timeline, reading each file once.
+    ///
+    /// A range that crosses from one file into the next is cut in parts, which are then joined
+    /// without re-encoding. `options` apply to every output.
+    pub fn trim_all(
+        &self,
+        ranges: &[(f64, f64, PathBuf)],
+        options: &TrimOptions,
+    ) -> Result<(), String> {
+        let mut per_file: Vec<Vec<(f64, f64, PathBuf)>> = vec![Vec::new(); self.files.len()];
+        let mut split = Vec::new();
+        for (start, end, output) in ranges {
+            if start >= end {
+                return Err(format!(
+                    "The end of {} must be after its start",
+                    output.display()
+                ));
+            }
+            let pieces = self.pieces(*start, *end);
+            if pieces.is_empty() {
+                return Err(format!("{} is outside the input files", output.display()));
+            }
+            split.push((pieces, output));
+        }
+        // Parts of ranges that cross into another file are cut here, then joined.
+        let work = if split.iter().any(|(pieces, _)| pieces.len() > 1) {
+            Some(WorkDir::next_to(&ranges[0].2)?)
+        } else {
+            None
+        };
+        let mut joins = Vec::new();
+        for (index, (pieces, output)) in split.into_iter().enumerate() {
+            if let [piece] = pieces.as_slice() {
+                per_file[piece.file].push((piece.start, piece.end, output.clone()));
+                continue;
+            }
+            let work = work
+                .as_ref()
+                .expect("Created for ranges with several pieces");
+            let parts: Vec<PathBuf> = pieces
+                .iter()
+                .map(|piece| {
+                    let part = work.join(format!("{index}.part{}.mp4", piece.file + 1));
+                    per_file[piece.file].push((piece.start, piece.end, part.clone()));
+                    part
+                })
+                .collect();
+            joins.push((parts, output));
+        }
+        self.files
+            .iter()
+            .zip(&per_file)
+            .filter(|(_, ranges)| !ranges.is_empty())
+            .try_for_each(|((input, _), ranges)| match ranges.as_slice() {
+                // A single range is quicker to seek to than to split out.
+                [(start, end, output)] => trim_video(
+                    FFMPEG_PATH,
+                    input,
+                    output.to_str().expect("Invalid filename"),
+                    &format_timestamp(*start),
+                    &format_timestamp(*end),
+                    options,
+                ),
+                _ => trim_segments(FFMPEG_PATH, input, ranges, options),
+            })
+            .and_then(|()| {
+                joins.iter().try_for_each(|(parts, output)| {
+                    concat_videos(FFMPEG_PATH, parts, output, &options.metadata)
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Files "a", "b" and "c", of 10, 20 and 5 seconds.
+    fn timeline() -> Timeline {
+        let files = [("a", 10.0), ("b", 20.0), ("c", 5.0)]
+            .into_iter()
+            .map(|(file, duration)| {
+                let info = VideoInfo {
+                    duration,
+                    ..VideoInfo::default()
+                };
+                (String::from(file), info)
+            })
+            .collect();
+        Timeline { files }
+    }
+
+    fn pieces(start: f64, end: f64) -> Vec<(usize, f64, f64)> {
+        timeline()
+            .pieces(start, end)
+            .iter()
+            .map(|piece| (piece.file, piece.start, piece.end))
+            .collect()
+    }
+
+    #[test]
+    fn duration_adds_up_the_files() {
+        assert_eq!(timeline().duration(), 35.0);
+    }
+
+    #[test]
+    fn files_start_where_the_previous_one_ends() {
+        let files: Vec<_> = timeline().files().map(|(_, offset)| offset).collect();
+        assert_eq!(files, [0.0, 10.0, 30.0]);
+    }
+
+    #[test]
+    fn locate_within_a_file() {
+        assert_eq!(timeline().locate(0.0), ("a", 0.0));
+        assert_eq!(timeline().locate(15.5), ("b", 5.5));
+    }
+
+    #[test]
+    fn locate_at_the_end_of_a_file_is_the_start_of_the_next() {
+        assert_eq!(timeline().locate(10.0), ("b", 0.0));
+        assert_eq!(timeline().locate(30.0), ("c", 0.0));
+    }
+
+    #[test]
+    fn locate_past_the_end_is_the_end_of_the_last_file() {
+        assert_eq!(timeline().locate(35.0), ("c", 5.0));
+        assert_eq!(timeline().locate(100.0), ("c", 5.0));
+    }
+
+    #[test]
+    fn range_within_one_file() {
+        assert_eq!(pieces(12.0, 20.0), [(1, 2.0, 10.0)]);
+    }
+
+    #[test]
+    fn range_touching_a_file_boundary_stays_in_one_file() {
+        assert_eq!(pieces(0.0, 10.0), [(0, 0.0, 10.0)]);
+        assert_eq!(pieces(10.0, 15.0), [(1, 0.0, 5.0)]);
+    }
+
+    #[test]
+    fn range_spanning_three_files() {
+        assert_eq!(
+            pieces(5.0, 32.0),
+            [(0, 5.0, 10.0), (1, 0.0, 20.0), (2, 0.0, 2.0)]
+        );
+    }
+
+    #[test]
+    fn range_past_the_end_is_cut_short() {
+        assert_eq!(pieces(30.0, 50.0), [(2, 0.0, 5.0)]);
+        assert_eq!(pieces(40.0, 50.0), []);
+    }
+
+    #[test]
+    fn trim_ending_before_it_starts_is_an_error() {
+        let options = TrimOptions::default();
+        for (start, end) in [(5.0, 5.0), (20.0, 12.0)] {
+            let error = timeline()
+                .trim_all(&[(start, end, PathBuf::from("set.mp4"))], &options)
+                .unwrap_err();
+            assert!(error.contains("must be after its start"), "{error}");
+        }
+    }
+}
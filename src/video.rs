@@ -1,5 +1,146 @@
+use std::collections::BTreeMap;
+use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Deserialize;
+
+use crate::filename::DEFAULT_FILENAME;
+
+pub const FFMPEG_PATH: &str = "static/ffmpeg.exe";
+
+/// Tells apart the work folders of jobs running at the same time.
+static NEXT_WORK_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// A new folder next to an output for the intermediate files of an ffmpeg pass, so they can't
+/// overwrite anything of the user's and can be moved into place without copying. It is removed,
+/// along with anything left in it, when dropped.
+pub struct WorkDir(PathBuf);
+
+impl WorkDir {
+    pub fn next_to(output: &Path) -> Result<WorkDir, String> {
+        let folder = output.parent().unwrap_or(Path::new("."));
+        let path = folder.join(format!(
+            ".work-{}-{}",
+            std::process::id(),
+            NEXT_WORK_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Unlike `create_dir_all`, this fails rather than reuse a folder that is already there.
+        fs::create_dir(&path).map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+        Ok(WorkDir(path))
+    }
+
+    pub fn join(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// How the streams of a trimmed set are written, beyond cutting them.
+#[derive(Clone, Debug, Default)]
+pub struct TrimOptions {
+    /// `-metadata` `(key, value)`s for the output.
+    pub metadata: Vec<(String, String)>,
+    /// What to do with each of the input's audio tracks, in order. When empty, every audio
+    /// track is kept as it is.
+    pub audio: Vec<AudioTrack>,
+}
+
+/// What to do with one of the input's audio tracks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrackMode {
+    #[default]
+    Keep,
+    Drop,
+    /// Mixed down with the other `Mix` tracks into a single track.
+    Mix,
+}
+
+impl TrackMode {
+    pub const ALL: [TrackMode; 3] = [TrackMode::Keep, TrackMode::Drop, TrackMode::Mix];
+}
+
+impl std::fmt::Display for TrackMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TrackMode::Keep => "Keep",
+            TrackMode::Drop => "Drop",
+            TrackMode::Mix => "Mix",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioTrack {
+    pub mode: TrackMode,
+    /// In dB.
+    pub gain: f64,
+    /// The input track's codec, which is kept if the track has to be re-encoded.
+    pub codec: String,
+}
+
+/// Used to re-encode audio whose codec we have no encoder for.
+const FALLBACK_AUDIO_ENCODER: &str = "aac";
+
+impl TrimOptions {
+    /// `-map` and codec arguments for the chosen audio tracks, to follow `-c copy`.
+    ///
+    /// Kept tracks without gain are copied. Anything that is mixed or has its gain changed has
+    /// to be re-encoded, and the mixed track comes after the kept ones. With no choices, every
+    /// audio track is copied, but not the data and timecode streams OBS adds, which MP4 can't
+    /// take when copying.
+    fn stream_args(&self) -> Vec<String> {
+        if self.audio.is_empty() {
+            return ["-map", "0:V", "-map", "0:a?"].map(String::from).to_vec();
+        }
+        let mut args: Vec<String> = ["-map", "0:V"].map(String::from).to_vec();
+        let mut filters = Vec::new();
+        let mut mixed = Vec::new();
+        let mut outputs = 0;
+        for (index, track) in self.audio.iter().enumerate() {
+            let volume = format!("[0:a:{index}]volume={}dB", track.gain);
+            match track.mode {
+                TrackMode::Drop => {}
+                TrackMode::Keep if track.gain == 0.0 => {
+                    args.extend([String::from("-map"), format!("0:a:{index}")]);
+                    outputs += 1;
+                }
+                TrackMode::Keep => {
+                    filters.push(format!("{volume}[keep{index}]"));
+                    args.extend([String::from("-map"), format!("[keep{index}]")]);
+                    args.extend([format!("-c:a:{outputs}"), reencoder(&track.codec)]);
+                    outputs += 1;
+                }
+                TrackMode::Mix => {
+                    filters.push(format!("{volume}[mix{index}]"));
+                    mixed.push((format!("[mix{index}]"), &track.codec));
+                }
+            }
+        }
+        if let Some((_, codec)) = mixed.first() {
+            let inputs: String = mixed.iter().map(|(label, _)| label.as_str()).collect();
+            // Without `normalize=0`, amix would quieten every track to make room for the others.
+            filters.push(format!(
+                "{inputs}amix=inputs={}:normalize=0[mix]",
+                mixed.len()
+            ));
+            args.extend(["-map", "[mix]"].map(String::from));
+            // The mix takes the codec of its first track.
+            args.extend([format!("-c:a:{outputs}"), reencoder(codec)]);
+        }
+        if !filters.is_empty() {
+            args.extend([String::from("-filter_complex"), filters.join(";")]);
+        }
+        args
+    }
+}
 
 pub fn trim_video(
     ffmpeg_path: impl AsRef<std::ffi::OsStr>,
@@ -7,22 +148,936 @@ pub fn trim_video(
     output_path: &str,
     start_time: &str,
     end_time: &str,
-) {
+    options: &TrimOptions,
+) -> Result<(), String> {
     let output = Command::new(ffmpeg_path)
-        .args([
-            "-ss",
+        .args(trim_args(
+            input_path,
+            output_path,
             start_time,
-            "-to",
             end_time,
-            "-i",
+            options,
+        ))
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!("Could not trim {input_path}"));
+    }
+    Ok(())
+}
+
+/// Arguments for `trim_video`'s ffmpeg call.
+fn trim_args(
+    input_path: &str,
+    output_path: &str,
+    start_time: &str,
+    end_time: &str,
+    options: &TrimOptions,
+) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-ss", start_time, "-to", end_time, "-i", input_path, "-c", "copy",
+    ]
+    .map(String::from)
+    .to_vec();
+    args.extend(options.stream_args());
+    args.extend(metadata_args(&options.metadata));
+    args.extend(["-y", output_path].map(String::from));
+    args
+}
+
+/// Decodes the frame at `time` and returns it as PNG bytes, scaled down to `width` if given.
+pub fn extract_frame(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    input_path: &str,
+    time: &str,
+    width: Option<u32>,
+) -> Result<Vec<u8>, String> {
+    let mut command = Command::new(ffmpeg_path);
+    command.args(["-ss", time, "-i", input_path, "-frames:v", "1"]);
+    if let Some(width) = width {
+        command.args(["-vf", &format!("scale={width}:-2")]);
+    }
+    let output = command
+        .args(["-f", "image2pipe", "-c:v", "png", "-"])
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        io::stderr().write_all(&output.stderr).unwrap();
+        return Err(format!("Could not extract a frame at {time}"));
+    }
+    Ok(output.stdout)
+}
+
+/// What ffmpeg reports about a video file.
+#[derive(Clone, Debug, Default)]
+pub struct VideoInfo {
+    /// In seconds.
+    pub duration: f64,
+    pub fps: f64,
+    pub codec: String,
+    pub pixel_format: String,
+    pub width: u32,
+    pub height: u32,
+    /// Audio streams, in order.
+    pub audio: Vec<AudioStream>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AudioStream {
+    pub codec: String,
+    pub sample_rate: u32,
+    /// Channel layout, such as "stereo".
+    pub layout: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+impl AudioStream {
+    /// Whether the two can be joined without re-encoding.
+    fn matches(&self, other: &AudioStream) -> bool {
+        (&self.codec, self.sample_rate, &self.layout)
+            == (&other.codec, other.sample_rate, &other.layout)
+    }
+}
+
+/// Used when ffmpeg doesn't report a frame rate.
+const DEFAULT_FPS: f64 = 30.0;
+
+/// Reads the details of `input_path` from ffmpeg's description of the file.
+pub fn probe_video(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    input_path: &str,
+) -> Result<VideoInfo, String> {
+    // Without an output file ffmpeg exits with an error, but still prints the stream details.
+    let output = Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-i", input_path])
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    let details = String::from_utf8_lossy(&output.stderr);
+    let duration = details
+        .split("Duration: ")
+        .nth(1)
+        .and_then(|rest| rest.split(',').next())
+        .and_then(parse_timestamp)
+        .ok_or_else(|| format!("Could not read the length of {input_path}"))?;
+    let mut info = VideoInfo {
+        duration,
+        fps: DEFAULT_FPS,
+        ..VideoInfo::default()
+    };
+    // Cover art shows up as a video stream too.
+    let video = details
+        .lines()
+        .filter(|line| !line.contains("(attached pic)"))
+        .find_map(|line| stream_fields(line, "Video"));
+    if let Some(fields) = video {
+        info.codec = first_word(fields[0]);
+        info.pixel_format = fields
+            .get(1)
+            .and_then(|field| field.split('(').next())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if let Some((width, height)) = fields.iter().find_map(|field| {
+            let (width, height) = field.split_whitespace().next()?.split_once('x')?;
+            Some((width.parse().ok()?, height.parse().ok()?))
+        }) {
+            (info.width, info.height) = (width, height);
+        }
+        info.fps = ["fps", "tbr"]
+            .iter()
+            .find_map(|unit| {
+                fields.iter().find_map(|field| {
+                    field
+                        .strip_suffix(unit)
+                        .and_then(|rate| rate.trim().parse::<f64>().ok())
+                })
+            })
+            .filter(|fps| *fps > 0.0)
+            .unwrap_or(DEFAULT_FPS);
+    }
+    // A stream's title is on a metadata line after it, so keep track of the stream we're in.
+    let mut in_audio = false;
+    for line in details.lines() {
+        if let Some(fields) = stream_fields(line, "Audio") {
+            info.audio.push(AudioStream {
+                codec: first_word(fields[0]),
+                sample_rate: fields
+                    .iter()
+                    .find_map(|field| field.strip_suffix(" Hz")?.parse().ok())
+                    .unwrap_or_default(),
+                layout: fields
+                    .get(2)
+                    .map(|field| first_word(field))
+                    .unwrap_or_default(),
+                language: line
+                    .split_once("): Audio")
+                    .and_then(|(stream, _)| stream.rsplit_once('('))
+                    .map(|(_, language)| language.to_string())
+                    .filter(|language| language != "und"),
+                title: None,
+            });
+            in_audio = true;
+        } else if line.trim_start().starts_with("Stream #") {
+            in_audio = false;
+        } else if let (true, Some((key, value))) = (in_audio, line.split_once(':')) {
+            if key.trim() == "title" {
+                if let Some(stream) = info.audio.last_mut() {
+                    stream.title = Some(value.trim().to_string());
+                }
+            }
+        }
+    }
+    Ok(info)
+}
+
+/// Splits the description of a `kind` ("Video" or "Audio") stream into its comma separated
+/// fields, leaving commas inside parentheses alone.
+fn stream_fields<'a>(line: &'a str, kind: &str) -> Option<Vec<&'a str>> {
+    if !line.trim_start().starts_with("Stream #") {
+        return None;
+    }
+    let (_, description) = line.split_once(&format!(": {kind}: "))?;
+    let mut fields = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, c) in description.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                fields.push(description[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(description[start..].trim());
+    Some(fields)
+}
+
+fn first_word(field: &str) -> String {
+    field
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Parses `HH:MM:SS`, `MM:SS` or `SS`, each optionally with a fractional part, into seconds.
+pub fn parse_timestamp(time: &str) -> Option<f64> {
+    time.trim().split(':').try_fold(0.0, |total, part| {
+        let part: f64 = part.parse().ok()?;
+        (part >= 0.0).then_some(total * 60.0 + part)
+    })
+}
+
+/// Formats seconds as `HH:MM:SS.mmm`, as accepted by ffmpeg.
+pub fn format_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Writes a `width` pixel wide JPEG into `dir` for every `interval` seconds of the video, named
+/// `000001.jpg`, `000002.jpg` and so on. Only keyframes are decoded, so this is far quicker than
+/// a full decode, at the cost of each tile being the nearest keyframe rather than the exact time.
+pub fn extract_tiles(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    input_path: &str,
+    interval: u32,
+    width: u32,
+    dir: &Path,
+) -> Result<(), String> {
+    let output = Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-skip_frame", "nokey", "-i", input_path])
+        .args(["-vf", &format!("fps=1/{interval},scale={width}:-2")])
+        .args(["-q:v", "5", "-y"])
+        .arg(dir.join("%06d.jpg"))
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    if !output.status.success() {
+        io::stderr().write_all(&output.stderr).unwrap();
+        return Err(format!("Could not build a filmstrip of {input_path}"));
+    }
+    Ok(())
+}
+
+/// Cuts several non-overlapping `(start, end, output)` ranges (in seconds) out of `input_path`
+/// in one pass, rather than seeking through the input once per range.
+///
+/// The input is read from the first start to the last end, the segment muxer splits it at
+/// every start and end in between, and the pieces between ranges are thrown away. As with `trim_video`, the streams are copied, so each cut lands on the
+/// next keyframe. `options` apply to every output.
+pub fn trim_segments(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    input_path: &str,
+    ranges: &[(f64, f64, PathBuf)],
+    options: &TrimOptions,
+) -> Result<(), String> {
+    let mut sorted: Vec<&(f64, f64, PathBuf)> = ranges.iter().collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (start, end, output) in &sorted {
+        if start >= end {
+            return Err(format!("{} ends before it starts", output.display()));
+        }
+    }
+    for pair in sorted.windows(2) {
+        if pair[1].0 < pair[0].1 {
+            return Err(format!(
+                "{} and {} overlap",
+                pair[0].2.display(),
+                pair[1].2.display()
+            ));
+        }
+    }
+    let Some(last_end) = sorted.iter().map(|range| range.1).reduce(f64::max) else {
+        return Ok(());
+    };
+    // Seeking skips demuxing everything before the first set, which may be hours of VOD.
+    let first_start = sorted[0].0;
+    let mut cuts: Vec<f64> = sorted
+        .iter()
+        .flat_map(|range| [range.0, range.1])
+        .filter(|time| *time > first_start && *time < last_end)
+        .collect();
+    cuts.dedup();
+
+    // The pieces between ranges are thrown away with the folder.
+    let pieces = WorkDir::next_to(&sorted[0].2)?;
+    // Segment times count from the seek point.
+    let segment_times = cuts
+        .iter()
+        .map(|time| format!("{:.3}", time - first_start))
+        .collect::<Vec<_>>()
+        .join(",");
+    let output = Command::new(ffmpeg_path)
+        .args(segment_args(
             input_path,
-            "-c",
-            "copy",
+            first_start,
+            last_end,
+            &segment_times,
+            options,
+        ))
+        .arg(pieces.join("%03d.mp4"))
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!("Could not split {input_path}"));
+    }
+    // Piece n runs from the nth cut (or the start of the input) to the next one.
+    for (start, _, output) in &sorted {
+        let index = cuts.iter().filter(|cut| *cut <= start).count();
+        fs::rename(pieces.join(format!("{index:03}.mp4")), output)
+            .map_err(|e| format!("Failed to write {}: {e}", output.display()))?;
+    }
+    Ok(())
+}
+
+/// Arguments for `trim_segments`'s ffmpeg call, up to the output pattern.
+fn segment_args(
+    input_path: &str,
+    first_start: f64,
+    last_end: f64,
+    segment_times: &str,
+    options: &TrimOptions,
+) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-hide_banner",
+        "-ss",
+        &format!("{first_start:.3}"),
+        "-to",
+        &format!("{last_end:.3}"),
+        "-i",
+        input_path,
+        "-c",
+        "copy",
+    ]
+    .map(String::from)
+    .to_vec();
+    args.extend(options.stream_args());
+    args.extend(["-f", "segment", "-reset_timestamps", "1"].map(String::from));
+    if !segment_times.is_empty() {
+        args.extend(["-segment_times", segment_times].map(String::from));
+    }
+    args.extend(metadata_args(&options.metadata));
+    args.push(String::from("-y"));
+    args
+}
+
+/// Joins `parts` end to end into `output` without re-encoding, so they must share codecs.
+pub fn concat_videos(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    parts: &[PathBuf],
+    output_path: &Path,
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    let work = WorkDir::next_to(output_path)?;
+    let list = work.join("concat.txt");
+    let entries: String = parts
+        .iter()
+        .map(|part| {
+            let part = fs::canonicalize(part).unwrap_or_else(|_| part.clone());
+            format!(
+                "file '{}'\n",
+                part.display().to_string().replace('\'', "'\\''")
+            )
+        })
+        .collect();
+    fs::write(&list, entries).map_err(|e| format!("Failed to write {}: {e}", list.display()))?;
+    let output = Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-f", "concat", "-safe", "0", "-i"])
+        .arg(&list)
+        .args(["-map", "0", "-c", "copy"])
+        .args(metadata_args(metadata))
+        .arg("-y")
+        .arg(output_path)
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!(
+            "Could not join the parts of {}",
+            output_path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Options for trimmed videos, set per template.
+#[derive(Deserialize, Clone, Debug)]
+pub struct VideoSettings {
+    /// Clip joined onto the start of every trimmed set.
+    #[serde(default)]
+    pub intro: Option<PathBuf>,
+    /// Clip joined onto the end of every trimmed set.
+    #[serde(default)]
+    pub outro: Option<PathBuf>,
+    /// Container metadata keys (such as `title`) and the text tokens that fill them in.
+    #[serde(default = "default_metadata")]
+    pub metadata: BTreeMap<String, String>,
+    /// Normalizes the loudness of each set's audio when set.
+    #[serde(default)]
+    pub loudness: Option<Loudness>,
+}
+
+/// Targets for EBU R128 loudness normalization.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Loudness {
+    /// Integrated loudness, in LUFS.
+    #[serde(default = "default_target")]
+    pub target: f64,
+    /// Maximum true peak, in dBTP.
+    #[serde(default = "default_true_peak")]
+    pub true_peak: f64,
+    /// Loudness range, in LU.
+    #[serde(default = "default_range")]
+    pub range: f64,
+}
+
+fn default_target() -> f64 {
+    -16.0
+}
+
+fn default_true_peak() -> f64 {
+    -1.5
+}
+
+fn default_range() -> f64 {
+    11.0
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings {
+            intro: None,
+            outro: None,
+            metadata: default_metadata(),
+            loudness: None,
+        }
+    }
+}
+
+fn default_metadata() -> BTreeMap<String, String> {
+    [
+        ("title", DEFAULT_FILENAME),
+        ("artist", "{player1}, {player2}"),
+        ("album", "{tournament}"),
+        ("date", "{date}"),
+        ("grouping", "{round}"),
+        (
+            "comment",
+            "{player1} ({fighter1}) vs {player2} ({fighter2}){score1|prefix: }{score2|prefix:-}",
+        ),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+/// `-metadata` arguments setting each `(key, value)`.
+fn metadata_args(metadata: &[(String, String)]) -> Vec<String> {
+    metadata
+        .iter()
+        .flat_map(|(key, value)| [String::from("-metadata"), format!("{key}={value}")])
+        .collect()
+}
+
+/// Writes `metadata` into the MP4 at `video_path`, in place and without re-encoding.
+pub fn write_metadata(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    video_path: &Path,
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    if metadata.is_empty() {
+        return Ok(());
+    }
+    let work = WorkDir::next_to(video_path)?;
+    let tagged = work.join("tagged.mp4");
+    let output = Command::new(ffmpeg_path)
+        .arg("-hide_banner")
+        .arg("-i")
+        .arg(video_path)
+        .args(["-map", "0", "-c", "copy"])
+        .args(metadata_args(metadata))
+        .arg("-y")
+        .arg(&tagged)
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!(
+            "Could not write the details into {}",
+            video_path.display()
+        ));
+    }
+    fs::rename(&tagged, video_path)
+        .map_err(|e| format!("Failed to write {}: {e}", video_path.display()))
+}
+
+/// Joins the `intro` and `outro` clips onto either end of the video at `video_path`, in place.
+///
+/// The set itself is never re-encoded. A clip is only re-encoded if its codecs, resolution or
+/// frame rate differ from the set's, so they can be joined. Returns a warning for each clip whose
+/// resolution or frame rate doesn't match.
+pub fn add_bumpers(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    video_path: &Path,
+    intro: Option<&Path>,
+    outro: Option<&Path>,
+    metadata: &[(String, String)],
+) -> Result<Vec<String>, String> {
+    if intro.is_none() && outro.is_none() {
+        return Ok(Vec::new());
+    }
+    let ffmpeg = ffmpeg_path.as_ref();
+    let video = probe_video(ffmpeg, video_path.to_str().expect("Invalid filename"))?;
+    let mut warnings = Vec::new();
+    let mut parts = vec![video_path.to_path_buf()];
+    let work = WorkDir::next_to(video_path)?;
+    [("intro", intro), ("outro", outro)]
+        .into_iter()
+        .filter_map(|(name, clip)| clip.map(|clip| (name, clip)))
+        .try_for_each(|(name, clip)| {
+            let info = probe_video(ffmpeg, clip.to_str().expect("Invalid filename"))?;
+            if (info.width, info.height) != (video.width, video.height) {
+                warnings.push(format!(
+                    "The {name} is {}x{} but the VOD is {}x{}.",
+                    info.width, info.height, video.width, video.height
+                ));
+            }
+            if (info.fps - video.fps).abs() > 0.01 {
+                warnings.push(format!(
+                    "The {name} is {} fps but the VOD is {} fps.",
+                    info.fps, video.fps
+                ));
+            }
+            let part = if can_join(&info, &video) {
+                clip.to_path_buf()
+            } else {
+                let output = work.join(format!("{name}.mp4"));
+                conform(ffmpeg, clip, &info, &video, &output)?;
+                output
+            };
+            if name == "intro" {
+                parts.insert(0, part);
+            } else {
+                parts.push(part);
+            }
+            Ok(())
+        })
+        .and_then(|()| {
+            let joined = work.join("joined.mp4");
+            concat_videos(ffmpeg, &parts, &joined, metadata)?;
+            fs::rename(&joined, video_path)
+                .map_err(|e| format!("Failed to write {}: {e}", video_path.display()))
+        })
+        .map(|()| warnings)
+}
+
+/// Whether `clip` can be joined to `video` without re-encoding.
+fn can_join(clip: &VideoInfo, video: &VideoInfo) -> bool {
+    clip.codec == video.codec
+        && clip.pixel_format == video.pixel_format
+        && (clip.width, clip.height) == (video.width, video.height)
+        && (clip.fps - video.fps).abs() <= 0.01
+        && clip.audio.len() == video.audio.len()
+        && clip
+            .audio
+            .iter()
+            .zip(&video.audio)
+            .all(|(clip, video)| clip.matches(video))
+}
+
+/// Re-encodes `clip` to the codecs, size and frame rate of `video`, letterboxing if needed.
+///
+/// The concat demuxer needs every file to have the same streams, so the output gets one audio
+/// track per track in `video`, taken from the clip's track in the same place or silent if the
+/// clip has fewer.
+fn conform(
+    ffmpeg: &std::ffi::OsStr,
+    clip: &Path,
+    info: &VideoInfo,
+    video: &VideoInfo,
+    output_path: &Path,
+) -> Result<(), String> {
+    let video_encoder = match video.codec.as_str() {
+        "h264" => "libx264",
+        "hevc" => "libx265",
+        "vp9" => "libvpx-vp9",
+        "av1" => "libsvtav1",
+        codec => return Err(format!("Can't re-encode {} as {codec}", clip.display())),
+    };
+    let (width, height) = (video.width, video.height);
+    let mut command = Command::new(ffmpeg);
+    command.arg("-hide_banner").arg("-i").arg(clip);
+    // Every input has to come before the output options.
+    let mut output_args: Vec<String> = ["-map", "0:v:0"].map(String::from).to_vec();
+    let mut silent_inputs = 0;
+    for (index, audio) in video.audio.iter().enumerate() {
+        let audio_encoder = audio_encoder(&audio.codec)
+            .ok_or_else(|| format!("Can't re-encode {} as {}", clip.display(), audio.codec))?;
+        if index < info.audio.len() {
+            output_args.extend([String::from("-map"), format!("0:a:{index}")]);
+        } else {
+            silent_inputs += 1;
+            command.args(["-f", "lavfi", "-i"]).arg(format!(
+                "anullsrc=r={}:cl={}",
+                audio.sample_rate, audio.layout
+            ));
+            output_args.extend([String::from("-map"), format!("{silent_inputs}:a:0")]);
+        }
+        output_args.extend([
+            format!("-filter:a:{index}"),
+            format!(
+                "aformat=sample_rates={}:channel_layouts={}",
+                audio.sample_rate, audio.layout
+            ),
+            format!("-c:a:{index}"),
+            String::from(audio_encoder),
+        ]);
+    }
+    if silent_inputs > 0 {
+        // Silence never ends, so stop at the end of the clip.
+        output_args.push(String::from("-shortest"));
+    }
+    let output = command
+        .args(output_args)
+        .args([
+            "-vf",
+            &format!(
+                "scale={width}:{height}:force_original_aspect_ratio=decrease,\
+                 pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={},format={}",
+                video.fps, video.pixel_format
+            ),
+            "-c:v",
+            video_encoder,
             "-y",
-            output_path,
         ])
+        .arg(output_path)
         .output()
-        .expect("ffmpeg call failed");
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
     io::stdout().write_all(&output.stdout).unwrap();
     io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!("Could not re-encode {}", clip.display()));
+    }
+    Ok(())
+}
+
+/// Attaches `image_path` to the MP4 at `video_path` as its cover art, in place.
+pub fn attach_cover(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    video_path: &Path,
+    image_path: &Path,
+) -> Result<(), String> {
+    let work = WorkDir::next_to(video_path)?;
+    let with_cover = work.join("cover.mp4");
+    let mut command = Command::new(ffmpeg_path);
+    command
+        .arg("-hide_banner")
+        .arg("-i")
+        .arg(video_path)
+        .arg("-i")
+        .arg(image_path)
+        // `V` skips any cover the video already has, so the new one is the second video stream.
+        .args(["-map", "0:V:0", "-map", "0:a?", "-map", "1", "-c", "copy"]);
+    if image_path.extension().is_some_and(|ext| ext == "webp") {
+        // MP4 covers can only be JPEG or PNG.
+        command.args(["-c:v:1", "png"]);
+    }
+    let output = command
+        .args(["-disposition:v:1", "attached_pic", "-y"])
+        .arg(&with_cover)
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!(
+            "Could not add the thumbnail to {}",
+            video_path.display()
+        ));
+    }
+    fs::rename(&with_cover, video_path)
+        .map_err(|e| format!("Failed to write {}: {e}", video_path.display()))
+}
+
+/// The encoder for audio from a track in `codec`, falling back to AAC.
+fn reencoder(codec: &str) -> String {
+    String::from(audio_encoder(codec).unwrap_or(FALLBACK_AUDIO_ENCODER))
+}
+
+/// The ffmpeg encoder for an audio codec, if there is one we can use.
+fn audio_encoder(codec: &str) -> Option<&'static str> {
+    match codec {
+        "aac" => Some("aac"),
+        "opus" => Some("libopus"),
+        "mp3" => Some("libmp3lame"),
+        "ac3" => Some("ac3"),
+        _ => None,
+    }
+}
+
+/// What the first pass of `loudnorm` measured about a set's audio.
+#[derive(Clone, Copy, Debug)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness, in LUFS.
+    pub integrated: f64,
+    /// True peak, in dBTP.
+    pub true_peak: f64,
+    /// Loudness range, in LU.
+    pub range: f64,
+    threshold: f64,
+    offset: f64,
+}
+
+/// Normalizes the loudness of every audio track of the video at `video_path` to `target`,
+/// in place, and returns what it measured beforehand for each track, in order.
+///
+/// Uses `loudnorm`'s two passes: the first measures each track, and the second applies a
+/// linear gain based on those measurements. Only the audio is re-encoded.
+pub fn normalize_loudness(
+    ffmpeg_path: impl AsRef<std::ffi::OsStr>,
+    video_path: &Path,
+    target: &Loudness,
+) -> Result<Vec<LoudnessMeasurement>, String> {
+    let ffmpeg = ffmpeg_path.as_ref();
+    let info = probe_video(ffmpeg, video_path.to_str().expect("Invalid filename"))?;
+    let targets = format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        target.target, target.true_peak, target.range
+    );
+    let measurements = (0..info.audio.len())
+        .map(|index| {
+            let output = Command::new(ffmpeg)
+                .args(["-hide_banner", "-nostats", "-i"])
+                .arg(video_path)
+                .args([
+                    "-map",
+                    &format!("0:a:{index}"),
+                    "-af",
+                    &format!("{targets}:print_format=json"),
+                ])
+                .args(["-f", "null", "-"])
+                .output()
+                .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+            let details = String::from_utf8_lossy(&output.stderr);
+            parse_loudness(&details).ok_or_else(|| {
+                format!(
+                    "Could not measure the loudness of track {} of {}",
+                    index + 1,
+                    video_path.display()
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut args = Vec::new();
+    for (index, (audio, measurement)) in info.audio.iter().zip(&measurements).enumerate() {
+        if !measurement.integrated.is_finite() {
+            // The track is silent, so there's nothing to normalize.
+            continue;
+        }
+        let encoder = audio_encoder(&audio.codec).ok_or_else(|| {
+            format!(
+                "Can't normalize the loudness of {} audio in {}",
+                audio.codec,
+                video_path.display()
+            )
+        })?;
+        args.extend([
+            format!("-filter:a:{index}"),
+            format!(
+                "{targets}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:\
+                 offset={}:linear=true",
+                measurement.integrated,
+                measurement.true_peak,
+                measurement.range,
+                measurement.threshold,
+                measurement.offset
+            ),
+            format!("-c:a:{index}"),
+            String::from(encoder),
+            // loudnorm works at 192 kHz, so go back to the original rate.
+            format!("-ar:a:{index}"),
+            audio.sample_rate.to_string(),
+        ]);
+    }
+    if args.is_empty() {
+        return Ok(measurements);
+    }
+
+    let work = WorkDir::next_to(video_path)?;
+    let normalized = work.join("loudnorm.mp4");
+    let output = Command::new(ffmpeg)
+        .arg("-hide_banner")
+        .arg("-i")
+        .arg(video_path)
+        .args(["-map", "0", "-c", "copy"])
+        .args(args)
+        .arg("-y")
+        .arg(&normalized)
+        .output()
+        .map_err(|e| format!("ffmpeg call failed: {e}"))?;
+    io::stdout().write_all(&output.stdout).unwrap();
+    io::stderr().write_all(&output.stderr).unwrap();
+    if !output.status.success() {
+        return Err(format!(
+            "Could not normalize the loudness of {}",
+            video_path.display()
+        ));
+    }
+    fs::rename(&normalized, video_path)
+        .map_err(|e| format!("Failed to write {}: {e}", video_path.display()))?;
+    Ok(measurements)
+}
+
+/// Reads the JSON summary `loudnorm` prints at the end of its first pass.
+fn parse_loudness(details: &str) -> Option<LoudnessMeasurement> {
+    let json = &details[details.rfind('{')?..=details.rfind('}')?];
+    let summary: serde_json::Value = serde_json::from_str(json).ok()?;
+    let value = |key: &str| summary.get(key)?.as_str()?.trim().parse::<f64>().ok();
+    Some(LoudnessMeasurement {
+        integrated: value("input_i")?,
+        true_peak: value("input_tp")?,
+        range: value("input_lra")?,
+        threshold: value("input_thresh")?,
+        offset: value("target_offset")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `-map`, `-filter_complex` and `-c:a` arguments in `args`, in order.
+    fn streams(args: &[String]) -> Vec<String> {
+        args.windows(2)
+            .filter(|pair| {
+                pair[0] == "-map" || pair[0] == "-filter_complex" || pair[0].starts_with("-c:a")
+            })
+            .flat_map(|pair| pair.iter().cloned())
+            .collect()
+    }
+
+    fn single(options: &TrimOptions) -> Vec<String> {
+        streams(&trim_args(
+            "in.mp4", "out.mp4", "00:00:10", "00:00:20", options,
+        ))
+    }
+
+    fn segmented(options: &TrimOptions) -> Vec<String> {
+        streams(&segment_args("in.mp4", 10.0, 40.0, "20.000", options))
+    }
+
+    #[test]
+    fn every_audio_track_is_kept_by_default() {
+        let options = TrimOptions::default();
+        assert_eq!(single(&options), ["-map", "0:V", "-map", "0:a?"]);
+        assert_eq!(segmented(&options), single(&options));
+    }
+
+    #[test]
+    fn both_paths_map_chosen_tracks_alike() {
+        let track = |mode, gain| AudioTrack {
+            mode,
+            gain,
+            codec: String::from("aac"),
+        };
+        let options = TrimOptions {
+            metadata: Vec::new(),
+            audio: vec![
+                track(TrackMode::Keep, 0.0),
+                track(TrackMode::Drop, 0.0),
+                track(TrackMode::Mix, -3.0),
+                track(TrackMode::Mix, 0.0),
+            ],
+        };
+        assert_eq!(
+            single(&options),
+            [
+                "-map",
+                "0:V",
+                "-map",
+                "0:a:0",
+                "-map",
+                "[mix]",
+                "-c:a:1",
+                "aac",
+                "-filter_complex",
+                "[0:a:2]volume=-3dB[mix2];[0:a:3]volume=0dB[mix3];\
+                 [mix2][mix3]amix=inputs=2:normalize=0[mix]",
+            ]
+        );
+        assert_eq!(segmented(&options), single(&options));
+    }
+
+    #[test]
+    fn work_folders_are_private_and_removed() {
+        let output = std::env::temp_dir().join("set.mp4");
+        let first = WorkDir::next_to(&output).unwrap();
+        let second = WorkDir::next_to(&output).unwrap();
+        assert_ne!(first.join("a.mp4"), second.join("a.mp4"));
+        fs::write(first.join("a.mp4"), b"").unwrap();
+        let folder = first.join("");
+        drop(first);
+        assert!(!folder.exists());
+    }
+
+    #[test]
+    fn segments_seek_to_the_first_start() {
+        let args = segment_args("in.mp4", 10.0, 40.0, "20.000", &TrimOptions::default());
+        assert_eq!(args[1..5], ["-ss", "10.000", "-to", "40.000"]);
+    }
 }